@@ -0,0 +1,26 @@
+//! Prints compression metrics for every strategy over a PGN database.
+//!
+//! Usage: cargo run --release --example benchmark_db -- <database.pgn>
+use anyhow::{anyhow, Result};
+use cgn::benchmark_utils::collect_metrics;
+use cgn::compression::{bincode, huffman};
+
+fn main() -> Result<()> {
+    let db_path = std::env::args()
+        .nth(1)
+        .ok_or(anyhow!("Usage: benchmark_db <database.pgn>"))?;
+
+    println!("bincode:");
+    collect_metrics(
+        &db_path,
+        bincode::compress_pgn_data,
+        bincode::decompress_pgn_data,
+    );
+    println!("huffman:");
+    collect_metrics(
+        &db_path,
+        huffman::compress_pgn_data,
+        huffman::decompress_pgn_data,
+    );
+    Ok(())
+}
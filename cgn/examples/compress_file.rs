@@ -0,0 +1,28 @@
+//! Compresses the first game of a PGN file using the huffman strategy.
+//!
+//! Usage: cargo run --example compress_file -- <input.pgn> <output.cgn>
+use anyhow::{anyhow, Result};
+use cgn::compression::huffman;
+use cgn::pgn_data::PgnData;
+use std::str::FromStr;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let (input, output) = match args.as_slice() {
+        [_, input, output] => (input, output),
+        _ => return Err(anyhow!("Usage: compress_file <input.pgn> <output.cgn>")),
+    };
+
+    // parse the game and compress it into bytes
+    let pgn_str = std::fs::read_to_string(input)?;
+    let pgn_data = PgnData::from_str(&pgn_str)?;
+    let compressed_data = huffman::compress_pgn_data(&pgn_data)?.to_bytes();
+    std::fs::write(output, &compressed_data)?;
+
+    println!(
+        "Compressed {} bytes into {} bytes",
+        pgn_str.len(),
+        compressed_data.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Compresses and decompresses a game with every strategy, checking the game is unchanged.
+//!
+//! Usage: cargo run --example roundtrip
+use anyhow::Result;
+use bit_vec::BitVec;
+use cgn::compression::{bincode, huffman};
+use cgn::pgn_data::PgnData;
+use std::str::FromStr;
+
+/// Example PGN string.
+const PGN_STR_EXAMPLE: &str = r#"[Event "Titled Tuesday Blitz January 03 Early 2023"]
+[Site ""]
+[Date "2023.01.03"]
+[Round "?"]
+[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "1-0"]
+
+1. a4 Nf6 2. d4 d5 3. Nf3 Bf5 4. Nh4 Be4 5. f3 Bg6 6. Nc3 c5 7. e4 cxd4 8. Nxg6
+hxg6 9. Qxd4 Nc6 10. Qf2 d4 11. Nd1 e5 12. Bc4 Rc8 13. Qe2 Bb4+ 14. Kf1 Na5 15.
+Bd3 O-O 16. Nf2 Qb6 17. h4 Nh5 18. Rh3 Qf6 19. g4 Nf4 20. Bxf4 Qxf4 21. h5 g5
+22. Rd1 a6 23. Kg2 Rc7 24. Rhh1 Rfc8 25. Nh3 Qf6 26. Ra1 Nc6 27. Rhc1 Bd6 28.
+Qd2 Bb4 29. c3 Be7 30. Nf2 dxc3 31. bxc3 Nd8 32. Bb1 Ne6 33. Nh3 Bc5 34. Ba2 Rd8
+35. Qe2 Nf4+ 36. Nxf4 gxf4 37. Kh3 g6 38. Rd1 Rcd7 39. Rxd7 Rxd7 40. Rd1 Bf2 41.
+Bxf7+ Kf8 42. Qxf2 Rxd1 43. Bxg6 Qd6 44. g5 Qd3 45. Qc5+ Qd6 46. Qc8+ Kg7 47.
+Qxb7+ Kf8 48. Qf7# 1-0"#;
+
+/// Compresses and decompresses the game, printing the compressed size.
+fn roundtrip(
+    name: &str,
+    pgn_data: &PgnData,
+    compress_fn: fn(&PgnData) -> Result<BitVec>,
+    decompress_fn: fn(&BitVec) -> Result<PgnData>,
+) -> Result<()> {
+    let compressed_data = compress_fn(pgn_data)?;
+    let decompressed_data = decompress_fn(&compressed_data)?;
+    assert_eq!(pgn_data.to_string(), decompressed_data.to_string());
+    println!("{}: {} bits", name, compressed_data.len());
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE)?;
+    println!("uncompressed: {} bits", PGN_STR_EXAMPLE.len() * 8);
+    roundtrip(
+        "bincode",
+        &pgn_data,
+        bincode::compress_pgn_data,
+        bincode::decompress_pgn_data,
+    )?;
+    roundtrip(
+        "huffman",
+        &pgn_data,
+        huffman::compress_pgn_data,
+        huffman::decompress_pgn_data,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,841 @@
+use crate::compression::{bincode, bincode_compact, delta_index, huffman};
+use crate::pgn_data::{PgnData, PgnHeaders};
+use anyhow::{anyhow, Result};
+use bit_vec::BitVec;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// A self-describing archive of compressed games, where each game is compressed with
+/// whichever strategy gives the smallest output.
+///
+/// The archive starts with the magic bytes `CGNA` and the number of records (u32),
+/// followed by each record:
+/// * The strategy tag (u8).
+/// * The bit length of the compressed game (u32).
+/// * The compressed game, zero padded to a whole number of bytes.
+///
+/// A game that duplicates an earlier game is instead stored as a back-reference record:
+/// * The `REFERENCE_TAG` (u8).
+/// * The index of the first occurrence of the game (u32).
+///
+/// All integers are big-endian.
+const MAGIC: &[u8; 4] = b"CGNA";
+
+/// The tag of a record referencing an earlier record, which no strategy uses.
+const REFERENCE_TAG: u8 = 0xFF;
+
+/// A single self-describing game, as created by `Strategy::compress_to_vec`, is the magic
+/// bytes `CGNG` followed by a single record in the same layout as the records of an archive.
+const GAME_MAGIC: &[u8; 4] = b"CGNG";
+
+/// A compression strategy that can be used for the records of an archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// The game is serialized with bincode and compressed with zlib.
+    Bincode,
+    /// The index of each move is Huffman coded with the Lichess weights.
+    Huffman,
+    /// The difference between the indices of consecutive moves by a side is Huffman coded.
+    DeltaIndex,
+    /// The game is stored as uncompressed PGN text.
+    Stored,
+    /// The game is serialized with bincode and compressed with zlib, storing each move
+    /// as its index rather than its SAN.
+    BincodeCompact,
+}
+
+/// Information about a compression strategy, e.g. for listing the strategies in a UI.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrategyInfo {
+    /// The name of the strategy, as used by the WASM exports.
+    pub name: &'static str,
+    /// A short description of how the strategy compresses games.
+    pub description: &'static str,
+    /// The size in bits of the 95 ply example game used in the tests with its headers cleared,
+    /// divided by its number of moves.
+    pub typical_bits_per_move: f64,
+    /// How fast the strategy compresses and decompresses games, relative to the others.
+    pub relative_speed: Speed,
+    /// Whether the output can be read without knowing the strategy used to create it.
+    pub self_describing: bool,
+}
+
+/// How fast a strategy compresses and decompresses games, relative to the other strategies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Speed {
+    /// Generates and orders the legal moves at every ply, several times slower than `Fast`.
+    Slow,
+    /// Serializes each move as its SAN, without generating the legal moves.
+    Fast,
+    /// Writes and parses the PGN text without any further encoding.
+    Fastest,
+}
+
+impl Strategy {
+    /// Every strategy that compresses games, in tag order, i.e. `all` without `Stored`.
+    /// These are the candidates an archive chooses from by default.
+    pub const COMPRESSING: [Strategy; 4] = [
+        Strategy::Bincode,
+        Strategy::Huffman,
+        Strategy::DeltaIndex,
+        Strategy::BincodeCompact,
+    ];
+
+    /// Returns every strategy the crate implements in tag order, i.e. `COMPRESSING` and `Stored`,
+    /// e.g. to list them in a UI or to read any archive record.
+    pub fn all() -> &'static [Strategy] {
+        &[
+            Strategy::Bincode,
+            Strategy::Huffman,
+            Strategy::DeltaIndex,
+            Strategy::Stored,
+            Strategy::BincodeCompact,
+        ]
+    }
+
+    /// Returns information about the strategy.
+    pub fn info(&self) -> StrategyInfo {
+        match self {
+            Strategy::Bincode => StrategyInfo {
+                name: "bincode",
+                description: "Serializes the game with bincode and compresses it with zlib.",
+                typical_bits_per_move: 25.3,
+                relative_speed: Speed::Fast,
+                self_describing: false,
+            },
+            Strategy::Huffman => StrategyInfo {
+                name: "huffman",
+                description: "Huffman codes the index of each move among the legal moves, \
+                    ordered by a move scoring heuristic.",
+                typical_bits_per_move: 5.0,
+                relative_speed: Speed::Slow,
+                self_describing: false,
+            },
+            Strategy::DeltaIndex => StrategyInfo {
+                name: "delta_index",
+                description: "Experimental. Huffman codes the difference between each move \
+                    index and the previous index of the same side.",
+                typical_bits_per_move: 6.0,
+                relative_speed: Speed::Slow,
+                self_describing: false,
+            },
+            Strategy::Stored => StrategyInfo {
+                name: "stored",
+                description: "Stores the game as uncompressed PGN text.",
+                typical_bits_per_move: 54.8,
+                relative_speed: Speed::Fastest,
+                self_describing: true,
+            },
+            Strategy::BincodeCompact => StrategyInfo {
+                name: "bincode_compact",
+                description: "Serializes the headers and the index of each move among the \
+                    legal moves with bincode, and compresses them with zlib.",
+                typical_bits_per_move: 8.4,
+                relative_speed: Speed::Slow,
+                self_describing: false,
+            },
+        }
+    }
+
+    /// Returns the tag stored in the archive for this strategy.
+    pub fn tag(self) -> u8 {
+        match self {
+            Strategy::Bincode => 0,
+            Strategy::Huffman => 1,
+            Strategy::DeltaIndex => 2,
+            Strategy::Stored => 3,
+            Strategy::BincodeCompact => 4,
+        }
+    }
+
+    /// Returns the strategy stored with the given tag.
+    pub fn from_tag(tag: u8) -> Result<Strategy> {
+        match tag {
+            0 => Ok(Strategy::Bincode),
+            1 => Ok(Strategy::Huffman),
+            2 => Ok(Strategy::DeltaIndex),
+            3 => Ok(Strategy::Stored),
+            4 => Ok(Strategy::BincodeCompact),
+            _ => Err(anyhow!("Unknown strategy tag {}", tag)),
+        }
+    }
+
+    /// Compresses a game with this strategy.
+    pub fn compress(self, pgn: &PgnData) -> Result<BitVec> {
+        match self {
+            Strategy::Bincode => bincode::compress_pgn_data(pgn),
+            Strategy::Huffman => huffman::compress_pgn_data(pgn),
+            Strategy::DeltaIndex => delta_index::compress_pgn_data(pgn),
+            Strategy::Stored => Ok(BitVec::from_bytes(pgn.to_string().as_bytes())),
+            Strategy::BincodeCompact => bincode_compact::compress_pgn_data(pgn),
+        }
+    }
+
+    /// Decompresses a game compressed with this strategy.
+    pub fn decompress(self, bit_vec: &BitVec) -> Result<PgnData> {
+        match self {
+            Strategy::Bincode => bincode::decompress_pgn_data(bit_vec),
+            Strategy::Huffman => huffman::decompress_pgn_data(bit_vec),
+            Strategy::DeltaIndex => delta_index::decompress_pgn_data(bit_vec),
+            Strategy::Stored => Ok(PgnData::from_str(&String::from_utf8(bit_vec.to_bytes())?)?),
+            Strategy::BincodeCompact => bincode_compact::decompress_pgn_data(bit_vec),
+        }
+    }
+
+    /// Decompresses a game compressed with this strategy, then checks that every move is legal,
+    /// returning an error with the ply and position of the first illegal move.
+    /// The move index strategies only ever decode legal moves, but corrupted bincode or stored
+    /// data can hold any SAN, so archives should use this when verifying their games.
+    pub fn decompress_checked(self, bit_vec: &BitVec) -> Result<PgnData> {
+        let pgn = self.decompress(bit_vec)?;
+        pgn.check_moves()?;
+        Ok(pgn)
+    }
+
+    /// Compresses a game with this strategy into self-describing bytes,
+    /// which can be decompressed with `Strategy::decompress_from_slice`.
+    pub fn compress_to_vec(self, pgn: &PgnData) -> Result<Vec<u8>> {
+        let mut bytes = GAME_MAGIC.to_vec();
+        write_record(&mut bytes, self, &self.compress(pgn)?)?;
+        Ok(bytes)
+    }
+
+    /// Decompresses a game created by `Strategy::compress_to_vec`, detecting the strategy used.
+    pub fn decompress_from_slice(bytes: &[u8]) -> Result<PgnData> {
+        let (strategy, start, bit_len) = read_game(bytes)?;
+        strategy.decompress(&record_bits(bytes, start, bit_len))
+    }
+
+    /// Returns the strategy a game created by `Strategy::compress_to_vec` was compressed with,
+    /// without decompressing it.
+    pub fn detect(bytes: &[u8]) -> Result<Strategy> {
+        Ok(read_game(bytes)?.0)
+    }
+}
+
+/// Options used when building an archive.
+#[derive(Clone, Debug)]
+pub struct ArchiveOptions {
+    /// The strategies tried for each game. The first strategy wins ties.
+    pub strategies: Vec<Strategy>,
+    /// Whether exact duplicates of earlier games, with the same headers and moves,
+    /// are stored as back-references to the first occurrence instead of being compressed again.
+    pub dedup: bool,
+}
+
+impl Default for ArchiveOptions {
+    /// Tries every strategy, without deduplicating games.
+    fn default() -> Self {
+        ArchiveOptions {
+            strategies: Strategy::COMPRESSING.to_vec(),
+            dedup: false,
+        }
+    }
+}
+
+/// A report of an archive built by `build_with_report`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveReport {
+    /// The number of games in the archive.
+    pub games: usize,
+    /// The number of games stored as back-references to an earlier duplicate.
+    pub duplicates: usize,
+}
+
+impl ArchiveReport {
+    /// Returns the fraction of games that were stored as back-references.
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.duplicates as f64 / self.games as f64
+        }
+    }
+}
+
+/// Compresses a game with each of the given strategies and returns the smallest output.
+/// Strategies that fail to compress the game are skipped.
+///
+/// The output is guaranteed to be no larger than the game as PGN text: if no strategy
+/// beats the PGN text, the game is returned with the `Stored` strategy instead.
+pub fn compress_best(pgn: &PgnData, strategies: &[Strategy]) -> Result<(Strategy, BitVec)> {
+    let stored = Strategy::Stored.compress(pgn)?;
+    let candidates = strategies
+        .iter()
+        .filter_map(|&s| s.compress(pgn).ok().map(|bit_vec| (s, bit_vec)));
+    Ok(smallest_or_stored(candidates, stored))
+}
+
+/// Returns the smallest candidate output, or the stored PGN text if every candidate is larger.
+fn smallest_or_stored(
+    candidates: impl Iterator<Item = (Strategy, BitVec)>,
+    stored: BitVec,
+) -> (Strategy, BitVec) {
+    candidates
+        .filter(|(_, bit_vec)| bit_vec.len() <= stored.len())
+        .min_by_key(|(_, bit_vec)| bit_vec.len())
+        .unwrap_or((Strategy::Stored, stored))
+}
+
+/// Builds an archive of the given games, compressing each with the smallest strategy.
+pub fn build<'a>(
+    games: impl IntoIterator<Item = &'a PgnData>,
+    options: ArchiveOptions,
+) -> Result<Vec<u8>> {
+    Ok(build_with_report(games, options)?.0)
+}
+
+/// Builds an archive of the given games like `build`, also reporting how many games were
+/// deduplicated.
+pub fn build_with_report<'a>(
+    games: impl IntoIterator<Item = &'a PgnData>,
+    options: ArchiveOptions,
+) -> Result<(Vec<u8>, ArchiveReport)> {
+    let mut records = Vec::new();
+    let mut count: u32 = 0;
+    let mut report = ArchiveReport::default();
+
+    // the index of the first occurrence of each game, by the content id of its moves
+    let mut first_occurrences: HashMap<u64, Vec<(u32, &PgnData)>> = HashMap::new();
+    for game in games {
+        let duplicate_of = if options.dedup {
+            let candidates = first_occurrences.entry(game.content_id()).or_default();
+            let duplicate_of = candidates
+                .iter()
+                .find(|(_, first)| first.diff(game).is_empty())
+                .map(|&(index, _)| index);
+            if duplicate_of.is_none() {
+                candidates.push((count, game));
+            }
+            duplicate_of
+        } else {
+            None
+        };
+
+        match duplicate_of {
+            Some(index) => {
+                records.push(REFERENCE_TAG);
+                records.extend(index.to_be_bytes());
+                report.duplicates += 1;
+            }
+            None => {
+                let (strategy, bit_vec) = compress_best(game, &options.strategies)?;
+                write_record(&mut records, strategy, &bit_vec)?;
+            }
+        }
+        count = count
+            .checked_add(1)
+            .ok_or(anyhow!("Archive exceeds {} games", u32::MAX))?;
+    }
+    report.games = count as usize;
+
+    let mut archive = MAGIC.to_vec();
+    archive.extend(count.to_be_bytes());
+    archive.append(&mut records);
+    Ok((archive, report))
+}
+
+/// A reader over an archive created by `build`.
+/// The records are indexed when the reader is created and decompressed on demand.
+/// Back-references are resolved when indexing, so duplicate games read like any other.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    records: Vec<(Strategy, usize, usize)>, // The strategy, byte offset and bit length of each record
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a reader over the bytes of an archive, checking that every record is complete.
+    pub fn new(bytes: &'a [u8]) -> Result<Reader<'a>> {
+        if bytes.len() < 8 || &bytes[..4] != MAGIC {
+            return Err(anyhow!("Not a CGN archive"));
+        }
+        let count = read_u32(bytes, 4)?;
+
+        // index the records
+        let mut pos = 8;
+        let mut records = Vec::new();
+        for _ in 0..count {
+            if bytes.get(pos) == Some(&REFERENCE_TAG) {
+                let index = read_u32(bytes, pos + 1)? as usize;
+                let record = *records.get(index).ok_or(anyhow!(
+                    "Reference to game {} is not to an earlier game",
+                    index
+                ))?;
+                records.push(record);
+                pos += 5;
+                continue;
+            }
+            let (strategy, start, bit_len) = read_record(bytes, pos)?;
+            pos = start + bit_len.div_ceil(8);
+            records.push((strategy, start, bit_len));
+        }
+        Ok(Reader { bytes, records })
+    }
+
+    /// Returns the number of games in the archive.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns true if the archive has no games.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns the strategy used to compress the game at the given index.
+    pub fn strategy(&self, index: usize) -> Option<Strategy> {
+        self.records.get(index).map(|(strategy, _, _)| *strategy)
+    }
+
+    /// Decompresses the game at the given index.
+    pub fn read(&self, index: usize) -> Result<PgnData> {
+        let &(strategy, start, bit_len) = self
+            .records
+            .get(index)
+            .ok_or(anyhow!("Game {} out of range", index))?;
+        strategy.decompress(&record_bits(self.bytes, start, bit_len))
+    }
+
+    /// Decompresses only the headers of the game at the given index.
+    /// Games compressed with the huffman or delta index strategies skip decoding their moves,
+    /// which makes listing the games of an archive much faster than reading them.
+    pub fn read_headers(&self, index: usize) -> Result<PgnHeaders> {
+        let &(strategy, start, bit_len) = self
+            .records
+            .get(index)
+            .ok_or(anyhow!("Game {} out of range", index))?;
+        let bit_vec = record_bits(self.bytes, start, bit_len);
+        match strategy {
+            Strategy::Huffman | Strategy::DeltaIndex => {
+                huffman::decompress_headers_only(&bit_vec.to_bytes())
+            }
+            _ => Ok(strategy.decompress(&bit_vec)?.headers),
+        }
+    }
+
+    /// Returns an iterator decompressing every game in the archive in order.
+    pub fn iter(&self) -> impl Iterator<Item = Result<PgnData>> + '_ {
+        (0..self.len()).map(|i| self.read(i))
+    }
+}
+
+/// Appends a record of a game compressed with the given strategy.
+fn write_record(bytes: &mut Vec<u8>, strategy: Strategy, bit_vec: &BitVec) -> Result<()> {
+    let bit_len = u32::try_from(bit_vec.len())
+        .map_err(|_| anyhow!("Compressed game exceeds {} bits", u32::MAX))?;
+    bytes.push(strategy.tag());
+    bytes.extend(bit_len.to_be_bytes());
+    bytes.extend(bit_vec.to_bytes());
+    Ok(())
+}
+
+/// Reads the record at the given byte offset, checking that it is complete.
+/// Returns the strategy, the byte offset of the compressed game and its bit length.
+fn read_record(bytes: &[u8], pos: usize) -> Result<(Strategy, usize, usize)> {
+    let tag = *bytes.get(pos).ok_or(anyhow!("Truncated record"))?;
+    let bit_len = read_u32(bytes, pos + 1)? as usize;
+    let start = pos + 5;
+    if start + bit_len.div_ceil(8) > bytes.len() {
+        return Err(anyhow!("Truncated record"));
+    }
+    Ok((Strategy::from_tag(tag)?, start, bit_len))
+}
+
+/// Reads the record of a game created by `Strategy::compress_to_vec`, checking its magic bytes
+/// and that nothing follows it.
+fn read_game(bytes: &[u8]) -> Result<(Strategy, usize, usize)> {
+    if bytes.len() < 4 || &bytes[..4] != GAME_MAGIC {
+        return Err(anyhow!("Not a CGN game"));
+    }
+    let (strategy, start, bit_len) = read_record(bytes, 4)?;
+    if start + bit_len.div_ceil(8) != bytes.len() {
+        return Err(anyhow!("Unexpected data after the game"));
+    }
+    Ok((strategy, start, bit_len))
+}
+
+/// Returns the bits of the compressed game of a record, without its padding.
+fn record_bits(bytes: &[u8], start: usize, bit_len: usize) -> BitVec {
+    let mut bit_vec = BitVec::from_bytes(&bytes[start..start + bit_len.div_ceil(8)]);
+    bit_vec.truncate(bit_len);
+    bit_vec
+}
+
+/// Reads a big-endian u32 at the given byte offset.
+fn read_u32(bytes: &[u8], pos: usize) -> Result<u32> {
+    let slice = bytes.get(pos..pos + 4).ok_or(anyhow!("Truncated record"))?;
+    Ok(u32::from_be_bytes([slice[0], slice[1], slice[2], slice[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Example PGN strings.
+    const PGN_STRS: [&str; 2] = [
+        r#"[Event "Example"]
+[Site ""]
+[Date "2023.01.03"]
+[Round "?"]
+[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "*"]
+
+1. e4 e5 2. Nf3 *"#,
+        r#"[Event ""]
+[Site ""]
+[Date ""]
+[Round ""]
+[White ""]
+[Black ""]
+[Result "0-1"]
+
+1. f3 e5 2. g4 Qh4# 0-1"#,
+    ];
+
+    /// The 95 ply example game the typical bits per move of each strategy are measured on.
+    const PGN_STR_EXAMPLE: &str = r#"[Event "Titled Tuesday Blitz January 03 Early 2023"]
+[Site ""]
+[Date "2023.01.03"]
+[Round "?"]
+[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "1-0"]
+
+1. a4 Nf6 2. d4 d5 3. Nf3 Bf5 4. Nh4 Be4 5. f3 Bg6 6. Nc3 c5 7. e4 cxd4 8. Nxg6
+hxg6 9. Qxd4 Nc6 10. Qf2 d4 11. Nd1 e5 12. Bc4 Rc8 13. Qe2 Bb4+ 14. Kf1 Na5 15.
+Bd3 O-O 16. Nf2 Qb6 17. h4 Nh5 18. Rh3 Qf6 19. g4 Nf4 20. Bxf4 Qxf4 21. h5 g5
+22. Rd1 a6 23. Kg2 Rc7 24. Rhh1 Rfc8 25. Nh3 Qf6 26. Ra1 Nc6 27. Rhc1 Bd6 28.
+Qd2 Bb4 29. c3 Be7 30. Nf2 dxc3 31. bxc3 Nd8 32. Bb1 Ne6 33. Nh3 Bc5 34. Ba2 Rd8
+35. Qe2 Nf4+ 36. Nxf4 gxf4 37. Kh3 g6 38. Rd1 Rcd7 39. Rxd7 Rxd7 40. Rd1 Bf2 41.
+Bxf7+ Kf8 42. Qxf2 Rxd1 43. Bxg6 Qd6 44. g5 Qd3 45. Qc5+ Qd6 46. Qc8+ Kg7 47.
+Qxb7+ Kf8 48. Qf7# 1-0"#;
+
+    /// Parses the example games.
+    fn games() -> Vec<PgnData> {
+        PGN_STRS
+            .iter()
+            .map(|s| PgnData::from_str(s).unwrap())
+            .collect()
+    }
+
+    #[test]
+    /// Tests that the typical bits per move of each strategy are measured on the example game.
+    fn typical_bits_per_move() {
+        let mut game = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        game.clear_headers();
+        for &strategy in Strategy::all() {
+            let bits = strategy.compress(&game).unwrap().len();
+            let bits_per_move = bits as f64 / game.moves.len() as f64;
+            let typical = strategy.info().typical_bits_per_move;
+            assert!((bits_per_move - typical).abs() < 0.05, "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    /// Tests that every strategy round trips a game with headers but no moves,
+    /// and a game with neither headers nor moves.
+    fn strategies_round_trip_no_moves() {
+        let mut game = games().remove(0);
+        game.moves.clear();
+        let mut empty = game.clone();
+        empty.clear_headers();
+        for &strategy in Strategy::all() {
+            for game in [&game, &empty] {
+                let bit_vec = strategy.compress(game).unwrap();
+                assert_eq!(game.diff(&strategy.decompress(&bit_vec).unwrap()), []);
+            }
+        }
+    }
+
+    #[test]
+    /// Tests that every game in an archive can be read back.
+    fn archive_round_trip() {
+        let games = games();
+        let archive = build(&games, ArchiveOptions::default()).unwrap();
+        let reader = Reader::new(&archive).unwrap();
+        assert_eq!(reader.len(), 2);
+        for (game, pgn_str) in reader.iter().zip(PGN_STRS) {
+            assert_eq!(game.unwrap().to_string(), pgn_str);
+        }
+    }
+
+    #[test]
+    /// Tests that each game is stored with the strategy giving the smallest output.
+    fn archive_picks_smallest_strategy() {
+        let games = games();
+        let archive = build(&games, ArchiveOptions::default()).unwrap();
+        let reader = Reader::new(&archive).unwrap();
+        for (i, game) in games.iter().enumerate() {
+            let (best, _) = compress_best(game, &Strategy::COMPRESSING).unwrap();
+            assert_eq!(reader.strategy(i), Some(best));
+        }
+    }
+
+    #[test]
+    /// Tests that the headers of every game can be read without the moves, with every strategy.
+    fn archive_read_headers() {
+        let games = games();
+        for &strategy in Strategy::all() {
+            let options = ArchiveOptions {
+                strategies: vec![strategy],
+                ..ArchiveOptions::default()
+            };
+            let archive = build(&games, options).unwrap();
+            let reader = Reader::new(&archive).unwrap();
+            for (i, game) in games.iter().enumerate() {
+                let headers = reader.read_headers(i).unwrap();
+                assert_eq!(headers.white, game.headers.white, "{strategy:?}");
+                assert_eq!(headers.result, game.headers.result, "{strategy:?}");
+            }
+            assert!(reader.read_headers(games.len()).is_err());
+        }
+    }
+
+    #[test]
+    /// Tests that the strategy of a self-describing game is detected without decompressing it.
+    fn detect_strategy() {
+        let game = &games()[1];
+        for &strategy in Strategy::all() {
+            let bytes = strategy.compress_to_vec(game).unwrap();
+            assert_eq!(Strategy::detect(&bytes).unwrap(), strategy);
+        }
+        let archive = build(&games(), ArchiveOptions::default()).unwrap();
+        assert!(Strategy::detect(&archive).is_err());
+        assert!(Strategy::detect(&[]).is_err());
+    }
+
+    #[test]
+    /// Tests that exact duplicates are stored as back-references and read back transparently.
+    fn archive_dedup() {
+        let games = games();
+        let mut renamed = games[0].clone();
+        renamed.headers.white = String::from("Hikaru Nakamura");
+        let all = [&games[0], &games[1], &games[0], &renamed, &games[1]];
+
+        let options = ArchiveOptions {
+            dedup: true,
+            ..ArchiveOptions::default()
+        };
+        let (archive, report) = build_with_report(all, options).unwrap();
+        assert_eq!(
+            report,
+            ArchiveReport {
+                games: 5,
+                duplicates: 2
+            }
+        );
+        assert_eq!(report.dedup_ratio(), 0.4);
+        let (full, report) = build_with_report(all, ArchiveOptions::default()).unwrap();
+        assert_eq!(report.duplicates, 0);
+        assert!(archive.len() < full.len());
+
+        let reader = Reader::new(&archive).unwrap();
+        assert_eq!(reader.len(), 5);
+        for (i, game) in all.iter().enumerate() {
+            assert_eq!(game.diff(&reader.read(i).unwrap()), [], "game {i}");
+            assert_eq!(reader.strategy(i), Reader::new(&full).unwrap().strategy(i));
+        }
+
+        // a reference to a game that isn't earlier in the archive is rejected
+        let mut invalid = MAGIC.to_vec();
+        invalid.extend(1u32.to_be_bytes());
+        invalid.push(REFERENCE_TAG);
+        invalid.extend(0u32.to_be_bytes());
+        assert!(Reader::new(&invalid).is_err());
+    }
+
+    #[test]
+    /// Tests that the archive only uses the strategies it is given.
+    fn archive_with_single_strategy() {
+        let games = games();
+        let options = ArchiveOptions {
+            strategies: vec![Strategy::Bincode],
+            ..ArchiveOptions::default()
+        };
+        let archive = build(&games, options).unwrap();
+        let reader = Reader::new(&archive).unwrap();
+        assert_eq!(reader.strategy(0), Some(Strategy::Bincode));
+        assert_eq!(reader.strategy(1), Some(Strategy::Bincode));
+    }
+
+    #[test]
+    /// Tests that a game is stored as PGN text when every strategy's output is larger than it.
+    fn archive_stored_fallback() {
+        let game = PgnData::from_str("1. e4 *").unwrap();
+        let stored = Strategy::Stored.compress(&game).unwrap();
+
+        // make every strategy's output one bit larger than the PGN text
+        let candidates = Strategy::COMPRESSING.iter().map(|&strategy| {
+            let mut bit_vec = strategy.compress(&game).unwrap();
+            bit_vec.grow(stored.len() + 1 - bit_vec.len(), false);
+            (strategy, bit_vec)
+        });
+        let (strategy, bit_vec) = smallest_or_stored(candidates, stored.clone());
+        assert_eq!(strategy, Strategy::Stored);
+        assert_eq!(bit_vec, stored);
+        assert_eq!(game.diff(&strategy.decompress(&bit_vec).unwrap()), []);
+
+        // an output the same size as the text is still preferred over storing it
+        let mut huffman = Strategy::Huffman.compress(&game).unwrap();
+        huffman.grow(stored.len() - huffman.len(), false);
+        let candidates = std::iter::once((Strategy::Huffman, huffman));
+        assert_eq!(smallest_or_stored(candidates, stored).0, Strategy::Huffman);
+    }
+
+    #[test]
+    /// Tests that the best output is never larger than the PGN text.
+    fn compress_best_never_exceeds_pgn_text() {
+        for game in games() {
+            for strategy in Strategy::COMPRESSING {
+                let (_, bit_vec) = compress_best(&game, &[strategy]).unwrap();
+                assert!(bit_vec.len() <= game.to_string().len() * 8);
+            }
+        }
+    }
+
+    #[test]
+    /// Tests that every strategy is listed once, in tag order, with a unique name.
+    fn strategy_list() {
+        let strategies = Strategy::all();
+        for (i, strategy) in strategies.iter().enumerate() {
+            assert_eq!(usize::from(strategy.tag()), i);
+            assert_eq!(Strategy::from_tag(strategy.tag()).unwrap(), *strategy);
+        }
+        let mut compressing = strategies.to_vec();
+        compressing.retain(|&s| s != Strategy::Stored);
+        assert_eq!(compressing, Strategy::COMPRESSING);
+
+        let mut names: Vec<&str> = strategies.iter().map(|s| s.info().name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), strategies.len());
+    }
+
+    #[test]
+    /// Tests that a single game round trips through bytes with every strategy.
+    fn game_bytes_round_trip() {
+        for game in games() {
+            for strategy in Strategy::all() {
+                let bytes = strategy.compress_to_vec(&game).unwrap();
+                let decompressed = Strategy::decompress_from_slice(&bytes).unwrap();
+                assert_eq!(game.diff(&decompressed), [], "{strategy:?}");
+            }
+        }
+    }
+
+    #[test]
+    /// Tests that random legal games round trip with every strategy, covering disambiguation,
+    /// promotions, castling and positions with many legal moves that a fixed game never reaches.
+    fn random_games_round_trip() {
+        // move ordering is slow in debug builds, so keep the number of plies modest
+        for seed in 1..=10 {
+            let game = crate::benchmark_utils::random_game(seed, 120);
+            for &strategy in Strategy::all() {
+                let bytes = strategy.compress_to_vec(&game).unwrap();
+                let decompressed = Strategy::decompress_from_slice(&bytes).unwrap();
+                assert_eq!(game.diff(&decompressed), [], "seed {seed}, {strategy:?}");
+            }
+        }
+    }
+
+    #[test]
+    /// Tests that an unfinished game without a result round trips with every strategy,
+    /// decoding exactly the stored moves rather than stopping early or reading past them.
+    fn game_without_result_round_trip() {
+        let game = PgnData::from_str("1. e4 e5 2. Nf3 Nc6 3. Bb5").unwrap();
+        assert!(game.headers.result.is_empty());
+        assert_eq!(game.moves.len(), 5);
+        for strategy in Strategy::all() {
+            let bytes = strategy.compress_to_vec(&game).unwrap();
+            let decompressed = Strategy::decompress_from_slice(&bytes).unwrap();
+            assert_eq!(decompressed.moves.len(), 5, "{strategy:?}");
+            assert_eq!(game.diff(&decompressed), [], "{strategy:?}");
+            assert_eq!(
+                PgnData::from_str(&decompressed.to_string())
+                    .unwrap()
+                    .moves
+                    .len(),
+                5
+            );
+        }
+    }
+
+    #[test]
+    /// Tests that a resigned game keeps the result stated by its `[Result]` tag with every strategy,
+    /// even though its final position isn't checkmate.
+    fn resigned_game_round_trip() {
+        let game =
+            PgnData::from_str("[Result \"1-0\"]\n\n1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 1-0").unwrap();
+        assert_eq!(game.headers.result, "1-0");
+        let final_pos = game.replay().unwrap().pop().unwrap();
+        assert!(shakmaty::Position::outcome(&final_pos).is_none());
+        for &strategy in Strategy::all() {
+            let decompressed = strategy
+                .decompress(&strategy.compress(&game).unwrap())
+                .unwrap();
+            assert_eq!(decompressed.headers.result, "1-0", "{strategy:?}");
+            assert_eq!(game.diff(&decompressed), [], "{strategy:?}");
+            assert!(
+                decompressed.to_string().ends_with("Nf6 1-0"),
+                "{strategy:?}"
+            );
+        }
+    }
+
+    #[test]
+    /// Tests that checked decompression rejects illegal moves and accepts legal games.
+    fn decompress_checked() {
+        let illegal = PgnData::from_str("1. e4 e5 2. Ke3 *").unwrap();
+        for strategy in [Strategy::Bincode, Strategy::Stored] {
+            let bit_vec = strategy.compress(&illegal).unwrap();
+            assert!(strategy.decompress(&bit_vec).is_ok());
+            let err = strategy.decompress_checked(&bit_vec).unwrap_err();
+            assert!(err.to_string().contains("ply 3"), "{err}");
+        }
+
+        // corrupted move bits either fail to decode or decode to legal moves,
+        // the headers are dropped so that every flipped bit lands in the moves
+        let mut game = games()[0].clone();
+        game.headers = PgnHeaders::new();
+        let bit_vec = Strategy::Huffman.compress(&game).unwrap();
+        for i in (bit_vec.len().saturating_sub(200)..bit_vec.len()).step_by(7) {
+            let mut corrupted = bit_vec.clone();
+            corrupted.set(i, !corrupted[i]);
+            if let Ok(pgn) = Strategy::Huffman.decompress(&corrupted) {
+                assert!(pgn.check_moves().is_ok());
+            }
+        }
+        assert!(Strategy::Huffman.decompress_checked(&bit_vec).is_ok());
+    }
+
+    #[test]
+    /// Tests that invalid and truncated game bytes are rejected.
+    fn game_bytes_invalid() {
+        let bytes = Strategy::Huffman.compress_to_vec(&games()[0]).unwrap();
+        assert!(Strategy::decompress_from_slice(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Strategy::decompress_from_slice(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(Strategy::decompress_from_slice(b"CGNA").is_err());
+
+        let mut bytes = bytes;
+        bytes[4] = 255;
+        assert!(Strategy::decompress_from_slice(&bytes).is_err());
+    }
+
+    #[test]
+    /// Tests that invalid and truncated archives are rejected.
+    fn archive_invalid() {
+        assert!(Reader::new(b"PGN!\0\0\0\0").is_err());
+        let archive = build(&games(), ArchiveOptions::default()).unwrap();
+        assert!(Reader::new(&archive[..archive.len() - 1]).is_err());
+    }
+
+    #[test]
+    /// Tests that an empty archive has no games.
+    fn archive_empty() {
+        let archive = build(&[], ArchiveOptions::default()).unwrap();
+        let reader = Reader::new(&archive).unwrap();
+        assert!(reader.is_empty());
+        assert!(reader.read(0).is_err());
+    }
+}
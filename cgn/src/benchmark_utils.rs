@@ -0,0 +1,576 @@
+use crate::compression::huffman::huffman_codes::{get_lichess_hashmap, lichess_huffman_weights};
+use crate::compression::huffman::score_move::generate_moves;
+use crate::pgn_data::{pgn_db_into_iter, PgnData, SanPlusWrapper};
+use anyhow::Result;
+use bit_vec::BitVec;
+use rayon::prelude::*;
+use shakmaty::{san::SanPlus, Chess, Position};
+use std::io::Write;
+use std::str::FromStr;
+
+///  Metrics for a compression strategy.
+/// * Time to compress game (seconds)
+/// * Time to decompress game (seconds)
+/// * Size of uncompressed game (total bits including headers)
+/// * Size of compressed game (total bits including headers)
+/// * Bits per move (total bits / number of moves)
+/// * Bits per move excluding headers (total move bits / number of moves)
+/// * Number of moves (plies) in the game
+pub struct Metrics {
+    time_to_compress: f64,
+    time_to_decompress: f64,
+    compressed_size: usize,
+    decompressed_size: usize,
+    bits_per_move: f64,
+    bits_per_move_excluding_headers: f64,
+    moves: usize,
+}
+
+/// Ply count buckets used to group games by length, as a label and an exclusive upper bound.
+const PLY_BUCKETS: [(&str, usize); 4] = [
+    ("0-20", 20),
+    ("20-40", 40),
+    ("40-80", 80),
+    ("80+", usize::MAX),
+];
+
+/// Summary of the metrics collected for a compression strategy.
+/// Every average is 0 when no metrics were collected, and the compression ratio is 0
+/// when the total decompressed size is 0, so a summary never contains NaN or infinity.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub games: usize,
+    pub avg_time_to_compress: f64,
+    pub avg_time_to_decompress: f64,
+    pub avg_compressed_size: usize,
+    pub avg_decompressed_size: usize,
+    pub avg_bits_per_move: f64,
+    pub avg_bits_per_move_excluding_headers: f64,
+    pub compression_ratio: f64,
+}
+
+/// Collect a single metric for a compression strategy.
+fn collect_single_metric(
+    pgn_str: &str,
+    compress_fn: fn(&PgnData) -> Result<BitVec>,
+    decompress_fn: fn(&BitVec) -> Result<PgnData>,
+) -> Result<Metrics> {
+    let pgn_data = PgnData::from_str(pgn_str)?;
+
+    // games with no moves compress fine, but have no bits per move, so skip them
+    if pgn_data.moves.is_empty() {
+        return Err(anyhow::anyhow!("Game is empty"));
+    }
+
+    // time to compress
+    let start = std::time::Instant::now();
+    let compressed_data = compress_fn(&pgn_data)?;
+    let end = std::time::Instant::now();
+    let time_to_compress = end.duration_since(start).as_secs_f64();
+
+    // compressed size
+    let compressed_size = compressed_data.len();
+
+    // time to decompress
+    let start = std::time::Instant::now();
+    let decompressed_data = decompress_fn(&compressed_data)?;
+    let end = std::time::Instant::now();
+    let time_to_decompress = end.duration_since(start).as_secs_f64();
+
+    // decompressed size
+    let decompressed_size = decompressed_data.to_string().len() * 8;
+
+    // bits per move
+    let bits_per_move = compressed_size as f64 / pgn_data.moves.len() as f64;
+
+    // bits per move excluding headers
+    let compressed_data_no_headers = compress_fn(&pgn_data.without_headers())?;
+    let bits_per_move_excluding_headers =
+        (compressed_data_no_headers.len()) as f64 / pgn_data.moves.len() as f64;
+
+    Ok(Metrics {
+        time_to_compress,
+        time_to_decompress,
+        compressed_size,
+        decompressed_size,
+        bits_per_move,
+        bits_per_move_excluding_headers,
+        moves: pgn_data.moves.len(),
+    })
+}
+
+/// Summarize the metrics collected for a compression strategy into averages.
+pub fn metrics_to_summary(metrics: &[Metrics]) -> Summary {
+    let games = metrics.len();
+    if games == 0 {
+        return Summary {
+            games,
+            avg_time_to_compress: 0.0,
+            avg_time_to_decompress: 0.0,
+            avg_compressed_size: 0,
+            avg_decompressed_size: 0,
+            avg_bits_per_move: 0.0,
+            avg_bits_per_move_excluding_headers: 0.0,
+            compression_ratio: 0.0,
+        };
+    }
+
+    // compute totals
+    let total_compressed_size = metrics.iter().map(|x| x.compressed_size).sum::<usize>();
+    let total_decompressed_size = metrics.iter().map(|x| x.decompressed_size).sum::<usize>();
+
+    // the ratio of the totals is the ratio of the averages, without the integer rounding
+    let compression_ratio = if total_decompressed_size == 0 {
+        0.0
+    } else {
+        total_compressed_size as f64 / total_decompressed_size as f64
+    };
+
+    // compute averages
+    Summary {
+        games,
+        avg_time_to_compress: metrics.iter().map(|x| x.time_to_compress).sum::<f64>()
+            / games as f64,
+        avg_time_to_decompress: metrics.iter().map(|x| x.time_to_decompress).sum::<f64>()
+            / games as f64,
+        avg_compressed_size: total_compressed_size / games,
+        avg_decompressed_size: total_decompressed_size / games,
+        avg_bits_per_move: metrics.iter().map(|x| x.bits_per_move).sum::<f64>() / games as f64,
+        avg_bits_per_move_excluding_headers: metrics
+            .iter()
+            .map(|x| x.bits_per_move_excluding_headers)
+            .sum::<f64>()
+            / games as f64,
+        compression_ratio,
+    }
+}
+
+/// Summarize the metrics collected for a compression strategy per game length bucket.
+/// Returns the label of each bucket with the summary of the games in it.
+pub fn metrics_to_summary_by_length(metrics: Vec<Metrics>) -> Vec<(&'static str, Summary)> {
+    let mut buckets: Vec<Vec<Metrics>> = PLY_BUCKETS.iter().map(|_| Vec::new()).collect();
+    for metric in metrics {
+        let i = PLY_BUCKETS
+            .iter()
+            .position(|(_, upper)| metric.moves < *upper)
+            .unwrap_or(PLY_BUCKETS.len() - 1);
+        buckets[i].push(metric);
+    }
+    PLY_BUCKETS
+        .iter()
+        .zip(buckets)
+        .map(|((label, _), bucket)| (*label, metrics_to_summary(&bucket)))
+        .collect()
+}
+
+impl Summary {
+    /// Returns the header and separator rows of a markdown table of summaries,
+    /// whose rows are given by `to_markdown_row`.
+    pub fn markdown_header() -> String {
+        String::from(
+            "| Strategy | Games | Compress (s) | Decompress (s) | Compressed bits | \
+             Decompressed bits | Bits per move | Bits per move excluding headers | Ratio |\n\
+             |---|---|---|---|---|---|---|---|---|",
+        )
+    }
+
+    /// Formats the summary as a row of a markdown table, labelled e.g. with the strategy name.
+    pub fn to_markdown_row(&self, label: &str) -> String {
+        format!(
+            "| {} | {} | {:.6} | {:.6} | {} | {} | {:.3} | {:.3} | {:.4} |",
+            label,
+            self.games,
+            self.avg_time_to_compress,
+            self.avg_time_to_decompress,
+            self.avg_compressed_size,
+            self.avg_decompressed_size,
+            self.avg_bits_per_move,
+            self.avg_bits_per_move_excluding_headers,
+            self.compression_ratio
+        )
+    }
+}
+
+impl std::fmt::Display for Summary {
+    /// Formats the summary as one metric per line.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "\tNumber of games benchmarked against: {}", self.games)?;
+        writeln!(
+            f,
+            "\tAverage time to compress: {} seconds",
+            self.avg_time_to_compress
+        )?;
+        writeln!(
+            f,
+            "\tAverage time to decompress: {} seconds",
+            self.avg_time_to_decompress
+        )?;
+        writeln!(
+            f,
+            "\tAverage compressed size: {} bits",
+            self.avg_compressed_size
+        )?;
+        writeln!(
+            f,
+            "\tAverage decompressed size: {} bits",
+            self.avg_decompressed_size
+        )?;
+        writeln!(f, "\tAverage bits per move: {}", self.avg_bits_per_move)?;
+        writeln!(
+            f,
+            "\tAverage bits per move excluding headers: {}",
+            self.avg_bits_per_move_excluding_headers
+        )?;
+        write!(f, "\tAverage compression ratio: {}", self.compression_ratio)
+    }
+}
+
+/// Returns the entropy of the Lichess move index weights in bits per move.
+/// This is the lower bound on the bits per move of any code that encodes each move index
+/// independently, assuming the move indices follow the Lichess distribution.
+pub fn lichess_entropy() -> f64 {
+    let weights = get_lichess_hashmap();
+    let total = weights.values().map(|w| f64::from(*w)).sum::<f64>();
+    weights
+        .values()
+        .map(|w| f64::from(*w) / total)
+        .filter(|p| *p > 0.0)
+        .map(|p| -p * p.log2())
+        .sum()
+}
+
+/// Returns the expected bits per move of the huffman strategy's code book,
+/// assuming the move indices follow the Lichess distribution.
+pub fn lichess_expected_code_length() -> f64 {
+    let weights = get_lichess_hashmap();
+    let (book, _) = lichess_huffman_weights();
+    let total = weights.values().map(|w| f64::from(*w)).sum::<f64>();
+    weights
+        .iter()
+        .map(|(index, w)| f64::from(*w) / total * book.get(index).map_or(0, |c| c.len()) as f64)
+        .sum()
+}
+
+/// Generates a random legal game of up to `max_plies` plies from a seed, picking a random
+/// legal move each ply with a xorshift generator, so the same seed always gives the same game.
+/// The game has no headers other than its result, which is set from its final position,
+/// or `*` if it didn't end on the board.
+pub fn random_game(seed: u64, max_plies: usize) -> PgnData {
+    let mut rng = XorShift::new(seed);
+    let mut pos = Chess::default();
+    let mut moves = Vec::new();
+    let plies = rng.next_usize(max_plies + 1);
+    for _ in 0..plies {
+        let legal_moves = pos.legal_moves();
+        if legal_moves.is_empty() {
+            break;
+        }
+        let m = &legal_moves[rng.next_usize(legal_moves.len())];
+        moves.push(SanPlusWrapper(SanPlus::from_move_and_play_unchecked(
+            &mut pos, m,
+        )));
+    }
+    let mut game = PgnData::from_moves(moves);
+    game.headers.result = String::from("*");
+    game.set_result_from_moves()
+        .expect("Generated moves are legal");
+    game
+}
+
+/// Writes a PGN database of `games` random legal games of up to 200 plies to the given path,
+/// deterministically from the seed. Each game has realistic seven tag roster headers,
+/// giving a reproducible benchmark input without a downloaded database.
+pub fn generate_test_db(path: &str, games: usize, seed: u64) -> Result<()> {
+    let mut rng = XorShift::new(seed);
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for i in 0..games {
+        let mut game = random_game(rng.next(), 200);
+        let white = rng.next_usize(1000);
+        let black = (white + 1 + rng.next_usize(999)) % 1000;
+        game.headers.event = String::from("Rated Blitz game");
+        game.headers.site = format!("https://example.org/{:08x}", rng.next() as u32);
+        game.headers.date = format!(
+            "2023.{:02}.{:02}",
+            1 + rng.next_usize(12),
+            1 + rng.next_usize(28)
+        );
+        game.headers.round = String::from("-");
+        game.headers.white = format!("Player{}", white);
+        game.headers.black = format!("Player{}", black);
+        if i > 0 {
+            writeln!(file)?;
+        }
+        game.write_pgn(&mut file)?;
+        writeln!(file)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// A xorshift pseudo-random number generator, used instead of a dependency so generated
+/// games are stable across platforms and versions.
+struct XorShift(u64);
+
+impl XorShift {
+    /// Creates a generator from a seed, replacing the invalid seed 0.
+    fn new(seed: u64) -> XorShift {
+        XorShift(seed.max(1))
+    }
+
+    /// Returns the next pseudo-random number.
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns the next pseudo-random number below `bound`.
+    fn next_usize(&mut self, bound: usize) -> usize {
+        self.next() as usize % bound
+    }
+}
+
+/// Collect the metrics for every game in a PGN database file.
+fn collect_all_metrics(
+    db_path: &str,
+    compress_fn: fn(&PgnData) -> Result<BitVec>,
+    decompress_fn: fn(&BitVec) -> Result<PgnData>,
+) -> Vec<Metrics> {
+    pgn_db_into_iter(db_path)
+        .expect("Failed to open PGN database file")
+        .par_bridge()
+        .take_any(1_000_000)
+        .map(|pgn_str| collect_single_metric(&pgn_str?, compress_fn, decompress_fn))
+        .filter_map(|x| x.ok())
+        .collect::<Vec<_>>()
+}
+
+/// Collect the metrics for every game in a PGN database file on a single thread, in file order.
+fn collect_all_metrics_seq(
+    db_path: &str,
+    compress_fn: fn(&PgnData) -> Result<BitVec>,
+    decompress_fn: fn(&BitVec) -> Result<PgnData>,
+) -> Vec<Metrics> {
+    pgn_db_into_iter(db_path)
+        .expect("Failed to open PGN database file")
+        .take(1_000_000)
+        .map(|pgn_str| collect_single_metric(&pgn_str?, compress_fn, decompress_fn))
+        .filter_map(|x| x.ok())
+        .collect::<Vec<_>>()
+}
+
+/// Collect the metrics for a compression strategy over a PGN database file and print a summary.
+/// Games are benchmarked in parallel, which is fastest but makes the timings noisy.
+pub fn collect_metrics(
+    db_path: &str,
+    compress_fn: fn(&PgnData) -> Result<BitVec>,
+    decompress_fn: fn(&BitVec) -> Result<PgnData>,
+) {
+    print_metrics(|| collect_all_metrics(db_path, compress_fn, decompress_fn));
+}
+
+/// Collect the metrics for a compression strategy over a PGN database file and print a summary.
+/// Games are benchmarked one at a time without rayon, so the time to compress and decompress
+/// each game is not affected by thread scheduling and can be compared across strategies.
+pub fn collect_metrics_seq(
+    db_path: &str,
+    compress_fn: fn(&PgnData) -> Result<BitVec>,
+    decompress_fn: fn(&BitVec) -> Result<PgnData>,
+) {
+    print_metrics(|| collect_all_metrics_seq(db_path, compress_fn, decompress_fn));
+}
+
+/// Collect metrics with the given function and print a summary of them.
+fn print_metrics(collect: impl FnOnce() -> Vec<Metrics>) {
+    let start = std::time::Instant::now();
+    let metrics = collect();
+    let end = std::time::Instant::now();
+
+    if metrics.is_empty() {
+        println!("\tNo metrics collected");
+        return;
+    }
+
+    println!(
+        "\tTotal time to benchmark: {} seconds",
+        end.duration_since(start).as_secs_f64()
+    );
+    let summary = metrics_to_summary(&metrics);
+    println!("{}", summary);
+    println!(
+        "\tHeadroom over the Lichess entropy bound: {} bits per move",
+        summary.avg_bits_per_move_excluding_headers - lichess_entropy()
+    );
+}
+
+/// Collect the metrics for a compression strategy over a PGN database file and print a summary
+/// for each game length bucket.
+pub fn collect_metrics_by_length(
+    db_path: &str,
+    compress_fn: fn(&PgnData) -> Result<BitVec>,
+    decompress_fn: fn(&BitVec) -> Result<PgnData>,
+) {
+    let metrics = collect_all_metrics(db_path, compress_fn, decompress_fn);
+    if metrics.is_empty() {
+        println!("\tNo metrics collected");
+        return;
+    }
+
+    for (label, summary) in metrics_to_summary_by_length(metrics) {
+        println!("\t[{} plies]", label);
+        println!("{}", summary);
+    }
+}
+
+/// Time legal move generation on its own and with move ordering over each position of a PGN
+/// database file, and print the totals. This shows how much of the time spent generating the
+/// ordered moves of a position goes into generating the legal moves.
+pub fn collect_move_generation_timings(db_path: &str) {
+    let Ok(db) = pgn_db_into_iter(db_path) else {
+        println!("\tNo metrics collected");
+        return;
+    };
+    let mut positions = Vec::new();
+    for pgn in db.filter_map(|pgn_str| PgnData::from_str(&pgn_str.ok()?).ok()) {
+        positions.extend(pgn.replay().into_iter().flatten());
+    }
+
+    let time = |f: &dyn Fn(&Chess) -> usize| {
+        let start = std::time::Instant::now();
+        let count: usize = positions.iter().map(f).sum();
+        (start.elapsed().as_secs_f64(), count)
+    };
+    let (legal_time, moves) = time(&|pos| pos.legal_moves().len());
+    let (ordered_time, _) = time(&|pos| generate_moves(pos).len());
+
+    println!("\tPositions: {}, legal moves: {}", positions.len(), moves);
+    println!("\tLegal move generation: {} seconds", legal_time);
+    println!(
+        "\tLegal move generation and ordering: {} seconds",
+        ordered_time
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Tests that a generated database is reproducible and its games parse and are legal.
+    fn generate_test_db_reproducible() {
+        let dir = std::env::temp_dir();
+        let paths = ["a", "b", "c"].map(|name| {
+            let path = dir.join(format!("cgn_test_db_{}_{}.pgn", std::process::id(), name));
+            path.to_str().unwrap().to_string()
+        });
+        generate_test_db(&paths[0], 3, 42).unwrap();
+        generate_test_db(&paths[1], 3, 42).unwrap();
+        generate_test_db(&paths[2], 3, 43).unwrap();
+        let contents = paths
+            .each_ref()
+            .map(|path| std::fs::read_to_string(path).unwrap());
+        for path in &paths {
+            std::fs::remove_file(path).unwrap();
+        }
+        assert_eq!(contents[0], contents[1]);
+        assert_ne!(contents[0], contents[2]);
+
+        let games: Vec<PgnData> =
+            crate::pgn_data::PgnDBIter::new(std::io::Cursor::new(contents[0].as_bytes()))
+                .map(|pgn_str| PgnData::from_str(&pgn_str.unwrap()).unwrap())
+                .collect();
+        assert_eq!(games.len(), 3);
+        for game in games {
+            assert!(game.check_moves().is_ok());
+            assert!(game.headers.white.starts_with("Player"));
+            assert_ne!(game.headers.white, game.headers.black);
+        }
+    }
+
+    /// Creates metrics for a game with the given sizes and no timings.
+    fn metrics_with_sizes(compressed_size: usize, decompressed_size: usize) -> Metrics {
+        Metrics {
+            time_to_compress: 0.0,
+            time_to_decompress: 0.0,
+            compressed_size,
+            decompressed_size,
+            bits_per_move: 0.0,
+            bits_per_move_excluding_headers: 0.0,
+            moves: 0,
+        }
+    }
+
+    #[test]
+    /// Tests that an empty set of metrics is summarized to zeros.
+    fn summary_of_no_metrics() {
+        let summary = metrics_to_summary(&[]);
+        assert_eq!(summary.games, 0);
+        assert_eq!(summary.avg_compressed_size, 0);
+        assert_eq!(summary.avg_bits_per_move, 0.0);
+        assert_eq!(summary.compression_ratio, 0.0);
+    }
+
+    #[test]
+    /// Tests that a zero decompressed size gives a compression ratio of 0 rather than NaN or infinity.
+    fn summary_of_zero_decompressed_size() {
+        let summary = metrics_to_summary(&[metrics_with_sizes(10, 0), metrics_with_sizes(0, 0)]);
+        assert_eq!(summary.games, 2);
+        assert_eq!(summary.compression_ratio, 0.0);
+        assert!(!summary.to_string().contains("NaN"));
+        assert!(!summary.to_string().contains("inf"));
+    }
+
+    #[test]
+    /// Tests that markdown rows have a cell for each column of the header.
+    fn summary_markdown_table() {
+        let summary = metrics_to_summary(&[metrics_with_sizes(1, 4), metrics_with_sizes(2, 4)]);
+        let header = Summary::markdown_header();
+        let row = summary.to_markdown_row("huffman");
+        assert_eq!(
+            row,
+            "| huffman | 2 | 0.000000 | 0.000000 | 1 | 4 | 0.000 | 0.000 | 0.3750 |"
+        );
+        let columns = |line: &str| line.matches('|').count();
+        let lines: Vec<&str> = header.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| columns(line) == columns(&row)));
+    }
+
+    #[test]
+    /// Tests that the compression ratio is computed from the totals.
+    fn summary_compression_ratio() {
+        let summary = metrics_to_summary(&[metrics_with_sizes(1, 4), metrics_with_sizes(2, 4)]);
+        assert_eq!(summary.avg_compressed_size, 1);
+        assert_eq!(summary.avg_decompressed_size, 4);
+        assert_eq!(summary.compression_ratio, 3.0 / 8.0);
+    }
+
+    #[test]
+    /// Tests that the huffman code book is within a bit of the entropy bound, as a Huffman code must be.
+    fn huffman_code_length_near_entropy() {
+        let entropy = lichess_entropy();
+        let code_length = lichess_expected_code_length();
+        assert!(entropy > 0.0 && entropy < 8.0);
+        assert!(code_length >= entropy);
+        assert!(code_length < entropy + 1.0);
+    }
+
+    #[test]
+    /// Tests that metrics are grouped into the bucket matching their game length.
+    fn summary_by_length() {
+        let metrics = [10, 20, 39, 40, 500]
+            .into_iter()
+            .map(|moves| Metrics {
+                moves,
+                ..metrics_with_sizes(1, 1)
+            })
+            .collect();
+        let summaries = metrics_to_summary_by_length(metrics);
+        let games: Vec<(&str, usize)> = summaries.iter().map(|(l, s)| (*l, s.games)).collect();
+        assert_eq!(
+            games,
+            vec![("0-20", 1), ("20-40", 2), ("40-80", 1), ("80+", 1)]
+        );
+    }
+}
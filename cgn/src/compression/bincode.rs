@@ -1,17 +1,47 @@
-use crate::{export_to_wasm, pgn_data::PgnData};
-use anyhow::Result;
+//! This strategy uses the bincode crate to serialize the data and
+//! then compresses it using the flate2 crate's ZlibEncoder at the
+//! best compression level.
+//!
+//! The compressed data starts with a version byte giving the layout of the serialized struct,
+//! since bincode is not self-describing and any change to PgnData would otherwise break all
+//! previously compressed data. Data from before the version byte was added starts directly
+//! with the zlib header, and is read with the original layout.
+
+use crate::pgn_data::PgnData;
+use crate::pgn_data::{PgnHeaders, SanPlusWrapper};
+use anyhow::{anyhow, Result};
 use bit_vec::BitVec;
-use std::str::FromStr;
-use wasm_bindgen::prelude::*;
 
-/// This strategy uses the bincode crate to serialize the data and
-/// then compresses it using the flate2 crate's ZlibEncoder at the
-/// best compression level.
+/// The version of the layout written by `compress_pgn_data`.
+/// Bump it whenever the fields of PgnData change, and keep a struct for the old layout.
+const FORMAT_VERSION: u8 = 1;
+
+/// The first byte of a zlib stream, which begins data written before the version byte was added.
+/// Versions must never use this value.
+pub(crate) const ZLIB_HEADER: u8 = 0x78;
+
+/// The original layout of PgnData, written without a version byte.
+#[derive(serde::Deserialize)]
+struct PgnDataV0 {
+    headers: PgnHeaders,
+    moves: Vec<SanPlusWrapper>,
+}
+
+impl From<PgnDataV0> for PgnData {
+    /// Migrates the original layout to the current PgnData struct.
+    fn from(data: PgnDataV0) -> Self {
+        PgnData {
+            headers: data.headers,
+            moves: data.moves,
+        }
+    }
+}
 
 /// Compresses the PGN data using bincode and ZlibEncoder at the maximum compression level.
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
 pub fn compress_pgn_data(pgn_data: &PgnData) -> Result<BitVec> {
     // create a buffer to store the compressed data and a ZlibEncoder
-    let mut compressed_data = Vec::new();
+    let mut compressed_data = vec![FORMAT_VERSION];
     let mut encoder =
         flate2::write::ZlibEncoder::new(&mut compressed_data, flate2::Compression::best());
 
@@ -21,19 +51,33 @@ pub fn compress_pgn_data(pgn_data: &PgnData) -> Result<BitVec> {
     Ok(BitVec::from_bytes(&compressed_data))
 }
 
-/// Decompresses the PGN data using bincode and ZlibDecoder.
+/// Decompresses the PGN data using bincode and ZlibDecoder, migrating older layouts.
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
 pub fn decompress_pgn_data(compressed_data: &BitVec) -> Result<PgnData> {
     let compressed_data_bytes = compressed_data.to_bytes();
-    let mut decoder = flate2::read::ZlibDecoder::new(compressed_data_bytes.as_slice());
-    Ok(bincode::deserialize_from(&mut decoder)?)
+    match compressed_data_bytes.first() {
+        Some(&FORMAT_VERSION) => {
+            let mut decoder = flate2::read::ZlibDecoder::new(&compressed_data_bytes[1..]);
+            Ok(bincode::deserialize_from(&mut decoder)?)
+        }
+        Some(&ZLIB_HEADER) => {
+            let mut decoder = flate2::read::ZlibDecoder::new(compressed_data_bytes.as_slice());
+            let data: PgnDataV0 = bincode::deserialize_from(&mut decoder)?;
+            Ok(data.into())
+        }
+        Some(version) => Err(anyhow!("Unsupported bincode format version {}", version)),
+        None => Err(anyhow!("Compressed data is empty")),
+    }
 }
 
 // Wrap the functions in a macro for export to WASM.
-export_to_wasm!("bincode", compress_pgn_data, decompress_pgn_data);
+#[cfg(feature = "wasm-bincode")]
+crate::export_to_wasm!("bincode", compress_pgn_data, decompress_pgn_data);
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
 
     /// Example PGN string.
     pub const PGN_STR_EXAMPLE: &str = r#"[Event "Titled Tuesday Blitz January 03 Early 2023"]
@@ -56,11 +100,48 @@ Qxb7+ Kf8 48. Qf7# 1-0"#;
     #[test]
     /// Test if the bincode Zlib compression is correct for PGN structs.
     fn bincode_pgn_data() {
-        let pgn_str = PGN_STR_EXAMPLE;
-        let pgn_data = PgnData::from_str(pgn_str).unwrap();
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
         let compressed_data = compress_pgn_data(&pgn_data).unwrap();
         let decompressed_data = decompress_pgn_data(&compressed_data).unwrap();
-        let decompressed_pgn_str = decompressed_data.to_string();
-        assert_eq!(pgn_str, decompressed_pgn_str);
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+    }
+
+    #[test]
+    /// Tests if the compressed data starts with the format version.
+    fn bincode_pgn_data_version() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let compressed_data = compress_pgn_data(&pgn_data).unwrap().to_bytes();
+        assert_eq!(compressed_data[0], FORMAT_VERSION);
+        assert_ne!(FORMAT_VERSION, ZLIB_HEADER);
+    }
+
+    #[test]
+    /// Tests if data written before the version byte was added is still decompressed.
+    fn bincode_pgn_data_unversioned() {
+        // `[White "A"] [Result "*"] 1. e4 e5 2. Nf3 *` compressed with the original layout
+        const FIXTURE: [u8; 48] = [
+            120, 218, 125, 137, 49, 17, 0, 48, 8, 3, 105, 235, 166, 107, 139, 0, 12, 224, 2, 252,
+            75, 96, 9, 12, 12, 100, 249, 191, 60, 209, 188, 5, 74, 63, 238, 129, 108, 208, 126, 25,
+            103, 83, 127, 1, 45, 234, 2, 146,
+        ];
+        let decompressed_data = decompress_pgn_data(&BitVec::from_bytes(&FIXTURE)).unwrap();
+        assert_eq!(decompressed_data.headers.white, "A");
+        assert_eq!(decompressed_data.headers.result, "*");
+        let moves: Vec<String> = decompressed_data
+            .moves
+            .iter()
+            .map(|san_plus| san_plus.0.to_string())
+            .collect();
+        assert_eq!(moves, ["e4", "e5", "Nf3"]);
+    }
+
+    #[test]
+    /// Tests if unknown format versions and empty data are rejected.
+    fn bincode_pgn_data_unknown_version() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let mut compressed_data = compress_pgn_data(&pgn_data).unwrap().to_bytes();
+        compressed_data[0] = FORMAT_VERSION + 1;
+        assert!(decompress_pgn_data(&BitVec::from_bytes(&compressed_data)).is_err());
+        assert!(decompress_pgn_data(&BitVec::new()).is_err());
     }
 }
@@ -0,0 +1,162 @@
+//! This strategy works like the bincode strategy, but instead of serializing each move
+//! as a SAN string, it serializes the index of the move among the legal moves of the
+//! replayed position (as ordered by the huffman strategy). No position has more than
+//! 218 legal moves, so each index fits in a single byte.
+//!
+//! Like the bincode strategy, the compressed data starts with a version byte giving the layout
+//! of the serialized struct, since it also serializes PgnHeaders. Data from before the version
+//! byte was added starts directly with the zlib header, and has the same layout as version 1.
+
+use crate::compression::bincode::ZLIB_HEADER;
+use crate::compression::huffman::score_move::{generate_moves, get_move_index};
+use crate::pgn_data::PgnData;
+use crate::pgn_data::{PgnHeaders, SanPlusWrapper};
+use anyhow::{anyhow, Result};
+use bit_vec::BitVec;
+use shakmaty::{san::SanPlus, Chess};
+
+/// The version of the layout written by `compress_pgn_data`.
+/// Bump it whenever the fields of CompactPgnData or PgnHeaders change,
+/// and keep a struct for the old layout.
+const FORMAT_VERSION: u8 = 1;
+
+/// The compact representation of a PGN game that is serialized.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CompactPgnData {
+    headers: PgnHeaders,
+    move_indices: Vec<u8>,
+}
+
+/// Converts the moves of a PGN game into their indices among the legal moves.
+fn moves_to_indices(pgn_data: &PgnData) -> Result<Vec<u8>> {
+    let positions = pgn_data.replay()?;
+    let mut move_indices = Vec::with_capacity(pgn_data.moves.len());
+    for (pos, san_plus) in positions.iter().zip(&pgn_data.moves) {
+        let m = san_plus.0.san.to_move(pos)?;
+        let index = get_move_index(pos, &m).ok_or_else(|| anyhow!("Move not found"))?;
+        move_indices.push(u8::try_from(index)?);
+    }
+    Ok(move_indices)
+}
+
+/// Converts move indices back into the moves of a PGN game.
+fn indices_to_moves(move_indices: &[u8]) -> Result<Vec<SanPlusWrapper>> {
+    let mut pos = Chess::default();
+    let mut san_plus_moves = Vec::with_capacity(move_indices.len());
+    for index in move_indices {
+        let moves = generate_moves(&pos);
+        let m = moves
+            .get(usize::from(*index))
+            .ok_or_else(|| anyhow!("Failed to decode move"))?;
+        let san_plus = SanPlus::from_move_and_play_unchecked(&mut pos, m);
+        san_plus_moves.push(SanPlusWrapper(san_plus));
+    }
+    Ok(san_plus_moves)
+}
+
+/// Compresses the PGN data using bincode on the move indices and ZlibEncoder at the maximum compression level.
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+pub fn compress_pgn_data(pgn_data: &PgnData) -> Result<BitVec> {
+    let compact = CompactPgnData {
+        headers: pgn_data.headers.clone(),
+        move_indices: moves_to_indices(pgn_data)?,
+    };
+
+    // create a buffer to store the compressed data and a ZlibEncoder
+    let mut compressed_data = vec![FORMAT_VERSION];
+    let mut encoder =
+        flate2::write::ZlibEncoder::new(&mut compressed_data, flate2::Compression::best());
+
+    // serialize the data into the encoder and finish the compression
+    bincode::serialize_into(&mut encoder, &compact)?;
+    encoder.finish()?;
+    Ok(BitVec::from_bytes(&compressed_data))
+}
+
+/// Decompresses the PGN data using bincode and ZlibDecoder, replaying the move indices.
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+pub fn decompress_pgn_data(compressed_data: &BitVec) -> Result<PgnData> {
+    let compressed_data_bytes = compressed_data.to_bytes();
+    let zlib_data = match compressed_data_bytes.first() {
+        Some(&FORMAT_VERSION) => &compressed_data_bytes[1..],
+        // data written before the version byte was added has the same layout as version 1
+        Some(&ZLIB_HEADER) => compressed_data_bytes.as_slice(),
+        Some(version) => {
+            return Err(anyhow!(
+                "Unsupported bincode_compact format version {}",
+                version
+            ))
+        }
+        None => return Err(anyhow!("Compressed data is empty")),
+    };
+    let mut decoder = flate2::read::ZlibDecoder::new(zlib_data);
+    let compact: CompactPgnData = bincode::deserialize_from(&mut decoder)?;
+    Ok(PgnData {
+        headers: compact.headers,
+        moves: indices_to_moves(&compact.move_indices)?,
+    })
+}
+
+// Wrap the functions in a macro for export to WASM.
+#[cfg(feature = "wasm-bincode-compact")]
+crate::export_to_wasm!("bincode_compact", compress_pgn_data, decompress_pgn_data);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Example PGN string.
+    const PGN_STR_EXAMPLE: &str = r#"[Event "Titled Tuesday Blitz January 03 Early 2023"]
+[Site ""]
+[Date "2023.01.03"]
+[Round "?"]
+[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "1-0"]
+
+1. a4 Nf6 2. d4 d5 3. Nf3 Bf5 4. Nh4 Be4 5. f3 Bg6 6. Nc3 c5 7. e4 cxd4 8. Nxg6
+hxg6 9. Qxd4 Nc6 10. Qf2 d4 11. Nd1 e5 12. Bc4 Rc8 13. Qe2 Bb4+ 14. Kf1 Na5 15.
+Bd3 O-O 16. Nf2 Qb6 17. h4 Nh5 18. Rh3 Qf6 19. g4 Nf4 20. Bxf4 Qxf4 21. h5 g5
+22. Rd1 a6 23. Kg2 Rc7 24. Rhh1 Rfc8 25. Nh3 Qf6 26. Ra1 Nc6 27. Rhc1 Bd6 28.
+Qd2 Bb4 29. c3 Be7 30. Nf2 dxc3 31. bxc3 Nd8 32. Bb1 Ne6 33. Nh3 Bc5 34. Ba2 Rd8
+35. Qe2 Nf4+ 36. Nxf4 gxf4 37. Kh3 g6 38. Rd1 Rcd7 39. Rxd7 Rxd7 40. Rd1 Bf2 41.
+Bxf7+ Kf8 42. Qxf2 Rxd1 43. Bxg6 Qd6 44. g5 Qd3 45. Qc5+ Qd6 46. Qc8+ Kg7 47.
+Qxb7+ Kf8 48. Qf7# 1-0"#;
+
+    #[test]
+    /// Test if the compact bincode Zlib compression is correct for PGN structs.
+    fn bincode_compact_pgn_data() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let compressed_data = compress_pgn_data(&pgn_data).unwrap();
+        let decompressed_data = decompress_pgn_data(&compressed_data).unwrap();
+        assert_eq!(PGN_STR_EXAMPLE, decompressed_data.to_string());
+    }
+
+    #[test]
+    /// Test if storing move indices is smaller than storing SAN strings.
+    fn bincode_compact_smaller_than_bincode() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let compact = compress_pgn_data(&pgn_data).unwrap();
+        let full = crate::compression::bincode::compress_pgn_data(&pgn_data).unwrap();
+        assert!(compact.len() < full.len());
+    }
+
+    #[test]
+    /// Tests if the compressed data starts with the format version, and data written before
+    /// the version byte was added is still decompressed.
+    fn bincode_compact_pgn_data_version() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let mut compressed_data = compress_pgn_data(&pgn_data).unwrap().to_bytes();
+        assert_eq!(compressed_data[0], FORMAT_VERSION);
+        assert_ne!(FORMAT_VERSION, ZLIB_HEADER);
+
+        let unversioned = BitVec::from_bytes(&compressed_data[1..]);
+        let decompressed_data = decompress_pgn_data(&unversioned).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+
+        compressed_data[0] = FORMAT_VERSION + 1;
+        assert!(decompress_pgn_data(&BitVec::from_bytes(&compressed_data)).is_err());
+        assert!(decompress_pgn_data(&BitVec::new()).is_err());
+    }
+}
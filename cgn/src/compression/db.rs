@@ -0,0 +1,139 @@
+use crate::archive::Strategy;
+use crate::pgn_data::PgnData;
+use anyhow::{anyhow, Result};
+use bit_vec::BitVec;
+use std::io::{ErrorKind, Read};
+
+/// An iterator that decompresses a database of compressed games one record at a time,
+/// so arbitrarily large databases can be read with constant memory.
+///
+/// Each record is a game compressed with the given strategy, stored as created by
+/// `to_padded_bytes` with an alignment of 1 (a big-endian u16 bit length followed by the bits),
+/// such as the records yielded by `PgnGameIterExt::compressed`.
+/// The iterator ends after an error, since the start of the next record is unknown.
+pub struct DecompressDbIter<R: Read> {
+    reader: R,
+    strategy: Strategy,
+    failed: bool, // Whether an error has been returned
+}
+
+impl<R: Read> DecompressDbIter<R> {
+    /// Creates a new DecompressDbIter over the given reader.
+    pub fn new(reader: R, strategy: Strategy) -> Self {
+        Self {
+            reader,
+            strategy,
+            failed: false,
+        }
+    }
+
+    /// Reads the next record, returning None at the end of the database.
+    fn read_record(&mut self) -> Result<Option<BitVec>> {
+        // read the length prefix, a clean end of file before it is the end of the database
+        let mut prefix = [0; 2];
+        let mut read = 0;
+        while read < prefix.len() {
+            match self.reader.read(&mut prefix[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(anyhow!("Record is missing its length prefix")),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // read the bits of the record
+        let bit_len = usize::from(u16::from_be_bytes(prefix));
+        let mut bytes = vec![0; bit_len.div_ceil(8)];
+        self.reader
+            .read_exact(&mut bytes)
+            .map_err(|_| anyhow!("Record is shorter than its length prefix"))?;
+        let mut bit_vec = BitVec::from_bytes(&bytes);
+        bit_vec.truncate(bit_len);
+        Ok(Some(bit_vec))
+    }
+}
+
+impl<R: Read> Iterator for DecompressDbIter<R> {
+    type Item = Result<PgnData>;
+
+    /// Read and decompress the next game in the database.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let game = self
+            .read_record()
+            .transpose()?
+            .and_then(|bit_vec| self.strategy.decompress(&bit_vec));
+        self.failed = game.is_err();
+        Some(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn_data::{PgnDBIter, PgnGameIterExt};
+    use std::io::Cursor;
+
+    /// Example PGN database.
+    const PGN_DB: &str = r#"[Event "Example"]
+[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 1-0
+
+[Event "Example"]
+[White "Hikaru Nakamura"]
+[Black "Magnus Carlsen"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+"#;
+
+    /// Compresses the example database into a buffer of records.
+    fn compressed_db(strategy: Strategy) -> Vec<u8> {
+        PgnDBIter::new(Cursor::new(PGN_DB))
+            .compressed(strategy)
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+            .concat()
+    }
+
+    #[test]
+    /// Tests that every record of a database is decompressed in order.
+    fn decompress_db_records() {
+        for strategy in Strategy::COMPRESSING {
+            let games: Vec<PgnData> =
+                DecompressDbIter::new(Cursor::new(compressed_db(strategy)), strategy)
+                    .collect::<Result<_>>()
+                    .unwrap();
+            assert_eq!(games.len(), 2);
+            assert_eq!(games[0].headers.white, "Magnus Carlsen");
+            assert_eq!(games[0].moves.len(), 3);
+            assert_eq!(games[1].headers.white, "Hikaru Nakamura");
+            assert_eq!(games[1].moves.len(), 2);
+        }
+    }
+
+    #[test]
+    /// Tests that an empty database yields no games.
+    fn decompress_empty_db() {
+        let mut iter = DecompressDbIter::new(Cursor::new(Vec::new()), Strategy::Huffman);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    /// Tests that a truncated record yields an error and ends the iterator.
+    fn decompress_truncated_db() {
+        let mut db = compressed_db(Strategy::Huffman);
+        db.pop();
+        let mut iter = DecompressDbIter::new(Cursor::new(db), Strategy::Huffman);
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+}
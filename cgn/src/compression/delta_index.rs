@@ -0,0 +1,222 @@
+//! This experimental strategy Huffman codes the difference between each move's index
+//! and the index of the previous move played by the same side, instead of the index itself.
+//! The difference is zig-zag encoded so small positive and negative differences get small symbols.
+//!
+//! The weights of each difference are derived from the Lichess move index weights,
+//! assuming the indices of consecutive moves by the same side are independent.
+//! The headers are stored in the same way as the huffman strategy.
+
+use crate::compression::huffman::decoder::{
+    check_max_plies, check_move_count, decompress_headers, split_headers_and_moves,
+};
+use crate::compression::huffman::encoder::{compress_headers, join_headers_and_moves};
+use crate::compression::huffman::huffman_codes::get_lichess_hashmap;
+use crate::compression::huffman::score_move::{generate_moves, get_move_index};
+use crate::pgn_data::{PgnData, SanPlusWrapper};
+use anyhow::{anyhow, Result};
+use bit_vec::BitVec;
+use huffman_compress::{Book, CodeBuilder, Tree};
+use shakmaty::{san::SanPlus, Chess, Color, Position};
+use std::sync::OnceLock;
+
+/// Zig-zag encodes a difference so that 0, -1, 1, -2, 2, ... map to 0, 1, 2, 3, 4, ...
+fn zigzag_encode(delta: i32) -> u16 {
+    ((delta << 1) ^ (delta >> 31)) as u16
+}
+
+/// Decodes a zig-zag encoded difference
+fn zigzag_decode(symbol: u16) -> i32 {
+    let symbol = i32::from(symbol);
+    (symbol >> 1) ^ -(symbol & 1)
+}
+
+/// The Huffman book and tree for the zig-zag encoded index differences, built on first use
+static DELTA_HUFFMAN_WEIGHTS: OnceLock<(Book<u16>, Tree<u16>)> = OnceLock::new();
+
+/// Get the Huffman book and tree for the zig-zag encoded index differences
+fn delta_huffman_weights() -> &'static (Book<u16>, Tree<u16>) {
+    DELTA_HUFFMAN_WEIGHTS.get_or_init(build_delta_huffman_weights)
+}
+
+/// Build the Huffman book and tree for the zig-zag encoded index differences
+fn build_delta_huffman_weights() -> (Book<u16>, Tree<u16>) {
+    let mut lichess_weights = vec![0u64; 256];
+    for (index, weight) in get_lichess_hashmap() {
+        lichess_weights[usize::from(index)] = u64::from(weight);
+    }
+
+    // the weight of a difference is the sum of the weights of every pair of indices giving it
+    let mut weights = vec![0u64; 511];
+    for (prev, prev_weight) in lichess_weights.iter().enumerate() {
+        for (next, next_weight) in lichess_weights.iter().enumerate() {
+            let delta = next as i32 - prev as i32;
+            let symbol = usize::from(zigzag_encode(delta));
+            weights[symbol] = weights[symbol].saturating_add(prev_weight * next_weight);
+        }
+    }
+
+    CodeBuilder::from_iter(
+        weights
+            .into_iter()
+            .enumerate()
+            .map(|(symbol, weight)| (symbol as u16, weight)),
+    )
+    .finish()
+}
+
+/// Returns the index into the previous indices for the side to move
+fn side_index(pos: &Chess) -> usize {
+    usize::from(pos.turn() == Color::Black)
+}
+
+/// Encode the moves of a PGN file as Huffman coded index differences
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+fn compress_moves(pgn: &PgnData) -> Result<BitVec> {
+    let (book, _) = delta_huffman_weights();
+    let positions = pgn.replay()?;
+    let mut prev_indices = [0i32; 2];
+    let mut bit_moves = BitVec::new();
+    for (pos, san_plus) in positions.iter().zip(&pgn.moves) {
+        let m = san_plus.0.san.to_move(pos)?;
+        let index = get_move_index(pos, &m).ok_or_else(|| anyhow!("Move not found"))?;
+        let index = i32::try_from(index)?;
+        if index > 255 {
+            return Err(anyhow!("Move index exceeds maximum value"));
+        }
+
+        // encode the difference from the previous index of the same side
+        let side = side_index(pos);
+        book.encode(&mut bit_moves, &zigzag_encode(index - prev_indices[side]))?;
+        prev_indices[side] = index;
+    }
+    Ok(bit_moves)
+}
+
+/// Decode exactly `move_count` moves of a PGN file from Huffman coded index differences,
+/// returning an error if there are more than `max_plies`
+fn decompress_moves(
+    move_bits: &BitVec,
+    move_count: usize,
+    max_plies: Option<usize>,
+) -> Result<Vec<SanPlusWrapper>> {
+    check_max_plies(move_count, max_plies)?;
+    let (_, tree) = delta_huffman_weights();
+    let mut pos = Chess::default();
+    let mut prev_indices = [0i32; 2];
+    let mut san_plus_moves = Vec::new();
+    for symbol in tree.decoder(move_bits, move_count) {
+        // recover the index from the difference to the previous index of the same side
+        let side = side_index(&pos);
+        let index = prev_indices[side] + zigzag_decode(symbol);
+        prev_indices[side] = index;
+
+        let moves = generate_moves(&pos);
+        let m = usize::try_from(index)
+            .ok()
+            .and_then(|i| moves.get(i))
+            .ok_or_else(|| anyhow!("Failed to decode move"))?;
+        let san_plus = SanPlus::from_move_and_play_unchecked(&mut pos, m);
+        san_plus_moves.push(SanPlusWrapper(san_plus));
+    }
+    check_move_count(move_count, san_plus_moves.len())?;
+    Ok(san_plus_moves)
+}
+
+/// Compress a PGN file using Huffman coded move index differences
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+pub fn compress_pgn_data(pgn: &PgnData) -> Result<BitVec> {
+    let headers = compress_headers(&pgn.headers)?;
+    let moves = compress_moves(pgn)?;
+    join_headers_and_moves(headers, moves, pgn.moves.len())
+}
+
+/// Decompress a PGN file compressed using Huffman coded move index differences
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+pub fn decompress_pgn_data(bit_vec: &BitVec) -> Result<PgnData> {
+    decompress_pgn_data_with(bit_vec, None)
+}
+
+/// Decompress a PGN file compressed using Huffman coded move index differences,
+/// returning an error if it has more than `max_plies` moves.
+pub fn decompress_pgn_data_with_max_plies(bit_vec: &BitVec, max_plies: usize) -> Result<PgnData> {
+    decompress_pgn_data_with(bit_vec, Some(max_plies))
+}
+
+/// Decompress a PGN file compressed using Huffman coded move index differences,
+/// returning an error if it has more than `max_plies` moves, if given
+fn decompress_pgn_data_with(bit_vec: &BitVec, max_plies: Option<usize>) -> Result<PgnData> {
+    let (headers, move_count, move_bits) = split_headers_and_moves(bit_vec, decompress_headers)?;
+    Ok(PgnData {
+        headers,
+        moves: decompress_moves(&move_bits, move_count, max_plies)?,
+    })
+}
+
+// Wrap the functions in a macro for export to WASM.
+#[cfg(feature = "wasm-delta-index")]
+crate::export_to_wasm!("delta_index", compress_pgn_data, decompress_pgn_data);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Example PGN string.
+    const PGN_STR_EXAMPLE: &str = r#"[Event "Titled Tuesday Blitz January 03 Early 2023"]
+[Site ""]
+[Date "2023.01.03"]
+[Round "?"]
+[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "1-0"]
+
+1. a4 Nf6 2. d4 d5 3. Nf3 Bf5 4. Nh4 Be4 5. f3 Bg6 6. Nc3 c5 7. e4 cxd4 8. Nxg6
+hxg6 9. Qxd4 Nc6 10. Qf2 d4 11. Nd1 e5 12. Bc4 Rc8 13. Qe2 Bb4+ 14. Kf1 Na5 15.
+Bd3 O-O 16. Nf2 Qb6 17. h4 Nh5 18. Rh3 Qf6 19. g4 Nf4 20. Bxf4 Qxf4 21. h5 g5
+22. Rd1 a6 23. Kg2 Rc7 24. Rhh1 Rfc8 25. Nh3 Qf6 26. Ra1 Nc6 27. Rhc1 Bd6 28.
+Qd2 Bb4 29. c3 Be7 30. Nf2 dxc3 31. bxc3 Nd8 32. Bb1 Ne6 33. Nh3 Bc5 34. Ba2 Rd8
+35. Qe2 Nf4+ 36. Nxf4 gxf4 37. Kh3 g6 38. Rd1 Rcd7 39. Rxd7 Rxd7 40. Rd1 Bf2 41.
+Bxf7+ Kf8 42. Qxf2 Rxd1 43. Bxg6 Qd6 44. g5 Qd3 45. Qc5+ Qd6 46. Qc8+ Kg7 47.
+Qxb7+ Kf8 48. Qf7# 1-0"#;
+
+    #[test]
+    /// Tests that zig-zag encoding interleaves negative and positive differences.
+    fn test_zigzag() {
+        assert_eq!(zigzag_encode(0), 0);
+        assert_eq!(zigzag_encode(-1), 1);
+        assert_eq!(zigzag_encode(1), 2);
+        assert_eq!(zigzag_encode(-255), 509);
+        assert_eq!(zigzag_encode(255), 510);
+        for delta in -255..=255 {
+            assert_eq!(zigzag_decode(zigzag_encode(delta)), delta);
+        }
+    }
+
+    #[test]
+    /// Test if the delta index compression is correct for PGN structs.
+    fn delta_index_pgn_data() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let compressed_data = compress_pgn_data(&pgn_data).unwrap();
+        let decompressed_data = decompress_pgn_data(&compressed_data).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+    }
+
+    #[test]
+    /// Test if the delta index compression is correct for a PGN with no headers.
+    fn delta_index_pgn_data_no_headers() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.clear_headers();
+        let compressed_data = compress_pgn_data(&pgn_data).unwrap();
+        let decompressed_data = decompress_pgn_data(&compressed_data).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+    }
+
+    #[test]
+    /// Test if decompression fails once a game exceeds the maximum number of plies.
+    fn delta_index_max_plies() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let compressed_data = compress_pgn_data(&pgn_data).unwrap();
+        assert!(decompress_pgn_data_with_max_plies(&compressed_data, 95).is_ok());
+        assert!(decompress_pgn_data_with_max_plies(&compressed_data, 94).is_err());
+    }
+}
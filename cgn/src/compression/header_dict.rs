@@ -0,0 +1,249 @@
+use crate::compression::utils::u8_to_bit_vec;
+use crate::pgn_data::{PgnData, PgnHeaders};
+use anyhow::{anyhow, Result};
+use bit_vec::BitVec;
+use std::collections::HashMap;
+
+/// A dictionary of header values shared across the games of a database.
+/// Player names, events and sites repeat heavily across a database, so instead of compressing
+/// each game's headers independently, values found in the dictionary are stored as an index.
+///
+/// Each of the seven headers is encoded as either:
+/// * [0] followed by the index of the value in the dictionary.
+/// * [1] followed by the byte length of the value (u16) and its UTF-8 bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeaderDict {
+    values: Vec<String>,             // The dictionary values in index order
+    indices: HashMap<String, usize>, // The index of each value
+}
+
+impl HeaderDict {
+    /// Builds a dictionary from the header values that appear more than once in the given games.
+    /// The values are ordered by frequency so the dictionary is deterministic.
+    pub fn train<'a>(games: impl IntoIterator<Item = &'a PgnData>) -> HeaderDict {
+        // count how often each header value appears
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for game in games {
+            for value in header_values(&game.headers) {
+                *counts.entry(value).or_insert(0) += 1;
+            }
+        }
+
+        // keep the repeated values, most frequent first
+        let mut values: Vec<(&str, usize)> = counts.into_iter().filter(|(_, c)| *c > 1).collect();
+        values.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        HeaderDict::from_values(values.into_iter().map(|(v, _)| v.to_string()).collect())
+    }
+
+    /// Creates a dictionary from a list of values, in index order.
+    pub fn from_values(values: Vec<String>) -> HeaderDict {
+        let indices = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v.clone(), i))
+            .collect();
+        HeaderDict { values, indices }
+    }
+
+    /// Serializes the dictionary so it can be stored once alongside the compressed games.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&self.values)?)
+    }
+
+    /// Deserializes a dictionary created by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<HeaderDict> {
+        Ok(HeaderDict::from_values(bincode::deserialize(bytes)?))
+    }
+
+    /// Returns the number of values in the dictionary.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if the dictionary has no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Number of bits used to store an index into the dictionary.
+    fn index_bits(&self) -> usize {
+        match self.values.len() {
+            0 | 1 => 0,
+            n => (usize::BITS - (n - 1).leading_zeros()) as usize,
+        }
+    }
+
+    /// Encodes the headers against the dictionary, padded to a whole number of bytes.
+    /// Empty headers are encoded as an empty bit vector.
+    pub fn encode(&self, headers: &PgnHeaders) -> Result<BitVec> {
+        if headers.is_empty() {
+            return Ok(BitVec::new());
+        }
+
+        let mut bit_vec = BitVec::new();
+        for value in header_values(headers) {
+            match self.indices.get(value) {
+                Some(&i) => {
+                    bit_vec.push(false);
+                    push_bits(&mut bit_vec, i, self.index_bits());
+                }
+                None => {
+                    let len = u16::try_from(value.len())
+                        .map_err(|_| anyhow!("Header value exceeds {} bytes", u16::MAX))?;
+                    bit_vec.push(true);
+                    push_bits(&mut bit_vec, usize::from(len), 16);
+                    for byte in value.bytes() {
+                        bit_vec.append(&mut u8_to_bit_vec(byte));
+                    }
+                }
+            }
+        }
+        Ok(BitVec::from_bytes(&bit_vec.to_bytes()))
+    }
+
+    /// Decodes headers encoded by `encode` with the same dictionary.
+    pub fn decode(&self, bytes: &[u8]) -> Result<PgnHeaders> {
+        let bit_vec = BitVec::from_bytes(bytes);
+        let mut pos = 0;
+        let mut values = Vec::with_capacity(7);
+        for _ in 0..7 {
+            if read_bits(&bit_vec, &mut pos, 1)? == 0 {
+                let i = read_bits(&bit_vec, &mut pos, self.index_bits())?;
+                let value = self
+                    .values
+                    .get(i)
+                    .ok_or_else(|| anyhow!("Header dictionary index {} out of range", i))?;
+                values.push(value.clone());
+            } else {
+                let len = read_bits(&bit_vec, &mut pos, 16)?;
+                let bytes = (0..len)
+                    .map(|_| read_bits(&bit_vec, &mut pos, 8).map(|b| b as u8))
+                    .collect::<Result<Vec<u8>>>()?;
+                values.push(String::from_utf8(bytes)?);
+            }
+        }
+
+        let mut values = values.into_iter();
+        let mut next = || values.next().unwrap_or_default();
+        Ok(PgnHeaders {
+            event: next(),
+            site: next(),
+            date: next(),
+            round: next(),
+            white: next(),
+            black: next(),
+            result: next(),
+        })
+    }
+}
+
+/// Returns the values of the seven headers in PGN order.
+fn header_values(headers: &PgnHeaders) -> [&str; 7] {
+    [
+        &headers.event,
+        &headers.site,
+        &headers.date,
+        &headers.round,
+        &headers.white,
+        &headers.black,
+        &headers.result,
+    ]
+}
+
+/// Pushes the lowest `len` bits of a value, most significant bit first.
+fn push_bits(bit_vec: &mut BitVec, value: usize, len: usize) {
+    for j in (0..len).rev() {
+        bit_vec.push((value >> j) & 1 == 1);
+    }
+}
+
+/// Reads `len` bits at the given position as an unsigned value, advancing the position.
+fn read_bits(bit_vec: &BitVec, pos: &mut usize, len: usize) -> Result<usize> {
+    let mut value = 0;
+    for _ in 0..len {
+        let bit = bit_vec
+            .get(*pos)
+            .ok_or_else(|| anyhow!("Unexpected end of dictionary encoded headers"))?;
+        value = (value << 1) | usize::from(bit);
+        *pos += 1;
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Creates a game with the given players and shared event headers.
+    fn game(white: &str, black: &str) -> PgnData {
+        let mut pgn_data = PgnData::from_str("1. e4 e5 *").unwrap();
+        pgn_data.headers = PgnHeaders {
+            event: "Titled Tuesday Blitz January 03 Early 2023".to_string(),
+            site: "https://www.chess.com".to_string(),
+            date: "2023.01.03".to_string(),
+            round: "?".to_string(),
+            white: white.to_string(),
+            black: black.to_string(),
+            result: "*".to_string(),
+        };
+        pgn_data
+    }
+
+    #[test]
+    /// Tests that only repeated values are added to the dictionary.
+    fn train_keeps_repeated_values() {
+        let games = [
+            game("Magnus Carlsen", "Hikaru Nakamura"),
+            game("Hikaru Nakamura", "Samvel Ter-Sahakyan"),
+        ];
+        let dict = HeaderDict::train(&games);
+        assert_eq!(dict.len(), 6);
+        assert!(dict.indices.contains_key("Hikaru Nakamura"));
+        assert!(!dict.indices.contains_key("Magnus Carlsen"));
+    }
+
+    #[test]
+    /// Tests that headers round trip, including values missing from the dictionary.
+    fn encode_decode_round_trip() {
+        let games = [
+            game("Magnus Carlsen", "Hikaru Nakamura"),
+            game("Hikaru Nakamura", "Magnus Carlsen"),
+        ];
+        let dict = HeaderDict::train(&games);
+        let headers = game("Magnus Carlsen", "Samvel Ter-Sahakyan").headers;
+        let encoded = dict.encode(&headers).unwrap();
+        let decoded = dict.decode(&encoded.to_bytes()).unwrap();
+        assert_eq!(decoded.white, "Magnus Carlsen");
+        assert_eq!(decoded.black, "Samvel Ter-Sahakyan");
+        assert_eq!(decoded.event, headers.event);
+    }
+
+    #[test]
+    /// Tests that headers found in the dictionary take only a few bytes.
+    fn encode_dictionary_values_is_small() {
+        let games = [
+            game("Magnus Carlsen", "Hikaru Nakamura"),
+            game("Hikaru Nakamura", "Magnus Carlsen"),
+        ];
+        let dict = HeaderDict::train(&games);
+        let encoded = dict.encode(&games[0].headers).unwrap();
+        assert!(encoded.to_bytes().len() <= 4);
+    }
+
+    #[test]
+    /// Tests that a dictionary survives being stored as bytes.
+    fn dictionary_to_and_from_bytes() {
+        let dict = HeaderDict::from_values(vec!["a".to_string(), "b".to_string()]);
+        let restored = HeaderDict::from_bytes(&dict.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored, dict);
+    }
+
+    #[test]
+    /// Tests that truncated headers are rejected.
+    fn decode_truncated() {
+        let values = ["a", "b", "c", "d"].map(String::from).to_vec();
+        let dict = HeaderDict::from_values(values);
+        assert!(dict.decode(&[0]).is_err());
+    }
+}
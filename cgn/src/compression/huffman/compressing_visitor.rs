@@ -0,0 +1,136 @@
+use super::encoder::{compress_headers, join_headers_and_moves, GameEncoder};
+use crate::pgn_data::PgnHeaders;
+use anyhow::Result;
+use bit_vec::BitVec;
+use shakmaty::Chess;
+
+/// A visitor that compresses a PGN game while it is being parsed.
+/// Each move is Huffman encoded as soon as it is read, so the moves of the game are never
+/// collected into a PgnData struct. The output is identical to `compress_pgn_data`.
+pub struct CompressingVisitor {
+    headers: PgnHeaders,          // The headers read so far
+    encoder: GameEncoder,         // The encoder holding the current position and encoded moves
+    error: Option<anyhow::Error>, // The first error found while encoding the moves
+}
+
+impl CompressingVisitor {
+    /// Creates a new CompressingVisitor.
+    pub fn new() -> CompressingVisitor {
+        CompressingVisitor {
+            headers: PgnHeaders::new(),
+            encoder: GameEncoder::new(),
+            error: None,
+        }
+    }
+}
+
+impl Default for CompressingVisitor {
+    /// Creates a new CompressingVisitor.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl pgn_reader::Visitor for CompressingVisitor {
+    /// The compressed game, or the first error found while compressing it.
+    type Result = Result<BitVec>;
+
+    /// Called when a game starts, resets the state left by the previous game.
+    fn begin_game(&mut self) {
+        self.headers = PgnHeaders::new();
+        self.encoder.pos = Chess::default();
+        self.encoder.bit_moves = BitVec::new();
+        self.encoder.move_count = 0;
+        self.error = None;
+    }
+
+    /// Called when a header is found in the PGN file.
+    fn header(&mut self, key: &[u8], value: pgn_reader::RawHeader<'_>) {
+        if let (Ok(key), Ok(value)) = (std::str::from_utf8(key), value.decode_utf8()) {
+            self.headers.set_tag(key, value.to_string());
+        }
+    }
+
+    /// Called when a move is found in the PGN file, encodes it straight away.
+    fn san(&mut self, san_plus: pgn_reader::SanPlus) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = san_plus
+            .san
+            .to_move(&self.encoder.pos)
+            .map_err(anyhow::Error::from)
+            .and_then(|m| self.encoder.encode(&m));
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    /// Called when a variation starts, variations are skipped.
+    fn begin_variation(&mut self) -> pgn_reader::Skip {
+        pgn_reader::Skip(true)
+    }
+
+    /// Called when the game ends, joins the compressed headers and moves.
+    fn end_game(&mut self) -> Self::Result {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let headers = compress_headers(&self.headers)?;
+        let moves = std::mem::take(&mut self.encoder.bit_moves);
+        join_headers_and_moves(headers, moves, self.encoder.move_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{compress_pgn_data, decompress_pgn_data};
+    use super::*;
+    use crate::pgn_data::PgnData;
+    use std::str::FromStr;
+
+    /// Example PGN string.
+    const PGN_STR_EXAMPLE: &str = r#"[Event "Titled Tuesday Blitz January 03 Early 2023"]
+[Site ""]
+[Date "2023.01.03"]
+[Round "?"]
+[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "1-0"]
+
+1. a4 Nf6 2. d4 d5 3. Nf3 Bf5 4. Nh4 Be4 5. f3 Bg6 6. Nc3 c5 7. e4 cxd4 8. Nxg6
+hxg6 9. Qxd4 Nc6 10. Qf2 d4 11. Nd1 e5 12. Bc4 Rc8 13. Qe2 Bb4+ 14. Kf1 Na5 15.
+Bd3 O-O 16. Nf2 Qb6 17. h4 Nh5 18. Rh3 Qf6 19. g4 Nf4 20. Bxf4 Qxf4 21. h5 g5
+22. Rd1 a6 23. Kg2 Rc7 24. Rhh1 Rfc8 25. Nh3 Qf6 26. Ra1 Nc6 27. Rhc1 Bd6 28.
+Qd2 Bb4 29. c3 Be7 30. Nf2 dxc3 31. bxc3 Nd8 32. Bb1 Ne6 33. Nh3 Bc5 34. Ba2 Rd8
+35. Qe2 Nf4+ 36. Nxf4 gxf4 37. Kh3 g6 38. Rd1 Rcd7 39. Rxd7 Rxd7 40. Rd1 Bf2 41.
+Bxf7+ Kf8 42. Qxf2 Rxd1 43. Bxg6 Qd6 44. g5 Qd3 45. Qc5+ Qd6 46. Qc8+ Kg7 47.
+Qxb7+ Kf8 48. Qf7# 1-0"#;
+
+    #[test]
+    /// Tests that compressing while parsing gives the same bits as compress_pgn_data.
+    fn visitor_matches_compress_pgn_data() {
+        let mut visitor = CompressingVisitor::new();
+        let compressed = pgn_reader::BufferedReader::new_cursor(PGN_STR_EXAMPLE)
+            .read_game(&mut visitor)
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        assert_eq!(compressed, compress_pgn_data(&pgn_data).unwrap());
+
+        let decompressed = decompress_pgn_data(&compressed).unwrap();
+        assert_eq!(decompressed.to_string(), PGN_STR_EXAMPLE);
+    }
+
+    #[test]
+    /// Tests that an illegal move is reported when the game ends.
+    fn visitor_illegal_move() {
+        let mut visitor = CompressingVisitor::new();
+        let compressed = pgn_reader::BufferedReader::new_cursor("1. e4 e4 *")
+            .read_game(&mut visitor)
+            .unwrap()
+            .unwrap();
+        assert!(compressed.is_err());
+    }
+}
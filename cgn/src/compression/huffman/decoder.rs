@@ -1,17 +1,20 @@
 use super::huffman_codes::lichess_huffman_weights;
 use super::score_move::generate_moves;
+use crate::compression::header_dict::HeaderDict;
+use crate::compression::utils::{bit_vec_to_u8, bit_vec_to_varint};
 use crate::pgn_data::{PgnData, PgnHeaders, SanPlusWrapper};
 use anyhow::{anyhow, Result};
 use bit_vec::BitVec;
 use flate2::read::ZlibDecoder;
 use shakmaty::{san::SanPlus, Chess};
 
-/// Gets the bit vector slice from start (inclusive) to end (exclusive)
+/// Gets the bit vector slice from start (inclusive) to end (exclusive).
+/// The slice may be empty, e.g. the moves of a game with no moves.
 fn get_bitvec_slice(bit_vec: &BitVec, start: usize, end: usize) -> Result<BitVec> {
     let len = bit_vec.len();
 
     // check for invalid indices
-    if (start > end) || (start >= len) || (end > len) {
+    if (start > end) || (end > len) {
         return Err(anyhow!("Invalid indices"));
     }
 
@@ -24,60 +27,160 @@ fn get_bitvec_slice(bit_vec: &BitVec, start: usize, end: usize) -> Result<BitVec
     Ok(result)
 }
 
+/// Decompress a PGN file
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
 pub fn decompress_pgn_data(bit_vec: &BitVec) -> Result<PgnData> {
-    // if the first bit is 1, then there are no headers, so just read the moves
-    if bit_vec[0] {
-        let move_bits = get_bitvec_slice(bit_vec, 1, bit_vec.len())?;
-        Ok(PgnData {
-            headers: PgnHeaders::new(),
-            moves: decompress_moves(&move_bits)?,
-        })
+    decompress_pgn_data_with(bit_vec, decompress_headers, None)
+}
+
+/// Decompress a PGN file, returning an error if it has more than `max_plies` moves.
+/// The stored move count is checked against `max_plies` before any move is decoded, and no
+/// moves are decoded if it is exceeded, bounding the time spent on untrusted input.
+pub fn decompress_pgn_data_with_max_plies(bit_vec: &BitVec, max_plies: usize) -> Result<PgnData> {
+    decompress_pgn_data_with(bit_vec, decompress_headers, Some(max_plies))
+}
+
+/// Decompress a PGN file whose headers were encoded against a dictionary shared across a database
+pub fn decompress_pgn_data_with_dict(bit_vec: &BitVec, dict: &HeaderDict) -> Result<PgnData> {
+    decompress_pgn_data_with(bit_vec, |bytes| dict.decode(bytes), None)
+}
+
+/// Decompress only the headers of a PGN file, without decoding its moves.
+/// This is much faster than `decompress_pgn_data` when only the headers are needed,
+/// e.g. to list the games of a database. Data from the delta_index strategy is also read,
+/// since it stores its headers the same way.
+pub fn decompress_headers_only(bytes: &[u8]) -> Result<PgnHeaders> {
+    Ok(split_headers(&BitVec::from_bytes(bytes), decompress_headers)?.0)
+}
+
+/// Decompress the headers of a PGN file compressed using ZLib
+pub(crate) fn decompress_headers(headers_bytes: &[u8]) -> Result<PgnHeaders> {
+    let mut decoder = ZlibDecoder::new(headers_bytes);
+    Ok(bincode::deserialize_from(&mut decoder)?)
+}
+
+/// Decompress a PGN file, decoding the header bytes with the given function
+fn decompress_pgn_data_with(
+    bit_vec: &BitVec,
+    decode_headers: impl Fn(&[u8]) -> Result<PgnHeaders>,
+    max_plies: Option<usize>,
+) -> Result<PgnData> {
+    let (headers, move_count, move_bits) = split_headers_and_moves(bit_vec, decode_headers)?;
+    Ok(PgnData {
+        headers,
+        moves: decompress_moves(&move_bits, move_count, max_plies)?,
+    })
+}
+
+/// Decompress the PGN file starting at bit `start` of the bit vector, which may be followed by
+/// other data, returning it and the bit index it ends at
+pub(crate) fn decompress_pgn_data_at(bit_vec: &BitVec, start: usize) -> Result<(PgnData, usize)> {
+    let (headers, headers_end) = split_headers_at(bit_vec, start, decompress_headers)?;
+    let (move_count, move_count_bits) = bit_vec_to_varint(bit_vec, headers_end)?;
+
+    // count the move bits as the decoder reads them, it never reads past the last move
+    let moves_start = headers_end + move_count_bits;
+    let mut move_bits_read = 0;
+    let move_bits = (moves_start..bit_vec.len())
+        .map(|i| bit_vec[i])
+        .inspect(|_| move_bits_read += 1);
+    let moves = decompress_moves(move_bits, move_count, None)?;
+    Ok((PgnData { headers, moves }, moves_start + move_bits_read))
+}
+
+/// Split a compressed PGN file into its decoded headers, its number of moves and its move bits
+pub(crate) fn split_headers_and_moves(
+    bit_vec: &BitVec,
+    decode_headers: impl Fn(&[u8]) -> Result<PgnHeaders>,
+) -> Result<(PgnHeaders, usize, BitVec)> {
+    let (headers, headers_end) = split_headers(bit_vec, decode_headers)?;
+    let (move_count, move_count_bits) = bit_vec_to_varint(bit_vec, headers_end)?;
+    let move_bits = get_bitvec_slice(bit_vec, headers_end + move_count_bits, bit_vec.len())?;
+    Ok((headers, move_count, move_bits))
+}
+
+/// Decode the headers of a compressed PGN file, returning them and the bit index they end at
+pub(crate) fn split_headers(
+    bit_vec: &BitVec,
+    decode_headers: impl Fn(&[u8]) -> Result<PgnHeaders>,
+) -> Result<(PgnHeaders, usize)> {
+    split_headers_at(bit_vec, 0, decode_headers)
+}
+
+/// Decode the headers of a compressed PGN file starting at bit `start`,
+/// returning them and the bit index they end at
+fn split_headers_at(
+    bit_vec: &BitVec,
+    start: usize,
+    decode_headers: impl Fn(&[u8]) -> Result<PgnHeaders>,
+) -> Result<(PgnHeaders, usize)> {
+    // if the first bit is 1, then there are no headers
+    let has_no_headers = bit_vec
+        .get(start)
+        .ok_or(anyhow!("Compressed data is empty"))?;
+    if has_no_headers {
+        Ok((PgnHeaders::new(), start + 1))
     }
     // if the first bit is 0, then there are headers, so read them
     else {
-        // get the header length in bytes from the first byte of the data
-        let header_bytes = bit_vec
-            .iter()
-            .take(8)
-            .enumerate()
-            .fold(
-                0,
-                |byte, (i, bit)| {
-                    if bit {
-                        byte | 1 << (7 - i)
-                    } else {
-                        byte
-                    }
-                },
-            );
-
-        // read the headers
-        let headers_bytes = get_bitvec_slice(bit_vec, 8, (header_bytes + 1) * 8)?.to_bytes();
-        let headers_slice = headers_bytes.as_slice();
-
-        // decompress the headers
-        let mut decoder = ZlibDecoder::new(headers_slice);
-        let headers: PgnHeaders = bincode::deserialize_from(&mut decoder)?;
-
-        let move_bits = get_bitvec_slice(bit_vec, (header_bytes + 1) * 8, bit_vec.len())?;
-        Ok(PgnData {
-            headers,
-            moves: decompress_moves(&move_bits)?,
-        })
+        // get the unsigned header length in bytes from the byte after the first bit
+        if bit_vec.len() < start + 9 {
+            return Err(anyhow!(
+                "Expected 8 bits of header length, got {}",
+                bit_vec.len() - start - 1
+            ));
+        }
+        let header_bytes = usize::from(bit_vec_to_u8(&get_bitvec_slice(
+            bit_vec,
+            start + 1,
+            start + 9,
+        )?)?);
+
+        // read and decode the headers
+        let headers_end = start + 9 + header_bytes * 8;
+        let headers_bytes = get_bitvec_slice(bit_vec, start + 9, headers_end)?.to_bytes();
+        let headers = decode_headers(headers_bytes.as_slice())?;
+        Ok((headers, headers_end))
     }
 }
 
-fn decompress_moves(move_bits: &BitVec) -> Result<Vec<SanPlusWrapper>> {
+/// Checks that a game of `move_count` moves is within `max_plies`, if given
+pub(crate) fn check_max_plies(move_count: usize, max_plies: Option<usize>) -> Result<()> {
+    match max_plies {
+        Some(max) if move_count > max => Err(anyhow!("Game exceeds the maximum of {} plies", max)),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `move_count` moves were decoded, rather than the move bits running out early
+pub(crate) fn check_move_count(move_count: usize, decoded: usize) -> Result<()> {
+    if decoded < move_count {
+        return Err(anyhow!("Expected {} moves, got {}", move_count, decoded));
+    }
+    Ok(())
+}
+
+/// Decode exactly `move_count` moves of a PGN file, ignoring any bits after them,
+/// returning an error if there are more than `max_plies`
+fn decompress_moves(
+    move_bits: impl IntoIterator<Item = bool>,
+    move_count: usize,
+    max_plies: Option<usize>,
+) -> Result<Vec<SanPlusWrapper>> {
+    check_max_plies(move_count, max_plies)?;
     let mut pos = Chess::default();
     let mut san_plus_moves = Vec::new();
-    for i in lichess_huffman_weights().1.decoder(move_bits, 256) {
+    for i in lichess_huffman_weights().1.decoder(move_bits, move_count) {
         let moves = generate_moves(&pos);
         let index: usize = i.try_into()?;
-        let m = moves.get(index).ok_or(anyhow!("Failed to decode move"))?;
+        let m = moves
+            .get(index)
+            .ok_or_else(|| anyhow!("Failed to decode move"))?;
         let san_plus = SanPlus::from_move_and_play_unchecked(&mut pos, m);
         let san_plus_wrapper = SanPlusWrapper(san_plus);
         san_plus_moves.push(san_plus_wrapper);
     }
+    check_move_count(move_count, san_plus_moves.len())?;
     Ok(san_plus_moves)
 }
 
@@ -96,6 +199,52 @@ mod tests {
         assert_eq!(get_bitvec_slice(&bit_vec, 0, 4).unwrap(), bit_vec);
     }
 
+    #[test]
+    /// Tests we can take an empty slice from the end of a bit vector
+    fn test_get_bitvec_slice_empty() {
+        let bit_vec = BitVec::from_elem(4, true);
+        assert_eq!(get_bitvec_slice(&bit_vec, 4, 4).unwrap(), BitVec::new());
+        assert!(get_bitvec_slice(&bit_vec, 5, 5).is_err());
+    }
+
+    #[test]
+    /// Tests that data too short to hold the header length is rejected
+    fn test_split_headers_and_moves_truncated_length() {
+        let bit_vec = BitVec::from_fn(3, |i| i == 1);
+        let err = split_headers_and_moves(&bit_vec, decompress_headers).unwrap_err();
+        assert_eq!(err.to_string(), "Expected 8 bits of header length, got 2");
+    }
+
+    #[test]
+    /// Tests that the headers are read without the moves, even if the moves are missing
+    fn test_decompress_headers_only() {
+        let mut pgn_data = PgnData::new();
+        pgn_data.headers.white = String::from("Magnus Carlsen");
+        pgn_data.headers.result = String::from("1-0");
+        let mut bit_vec = super::super::compress_pgn_data(&pgn_data).unwrap();
+        let headers = decompress_headers_only(&bit_vec.to_bytes()).unwrap();
+        assert_eq!(headers.white, "Magnus Carlsen");
+        assert_eq!(headers.result, "1-0");
+
+        // drop the move count, which a full decompression needs
+        bit_vec.truncate(bit_vec.len() - 8);
+        assert!(decompress_headers_only(&bit_vec.to_bytes()).is_ok());
+        assert!(super::super::decompress_pgn_data(&bit_vec).is_err());
+
+        // data without headers gives empty headers
+        let bit_vec = BitVec::from_elem(9, true);
+        assert!(decompress_headers_only(&bit_vec.to_bytes())
+            .unwrap()
+            .is_empty());
+        assert!(decompress_headers_only(&[]).is_err());
+    }
+
+    #[test]
+    /// Tests that empty data is rejected rather than panicking
+    fn test_split_headers_and_moves_empty() {
+        assert!(split_headers_and_moves(&BitVec::new(), decompress_headers).is_err());
+    }
+
     #[test]
     /// Tests we can take a subslice of a bit vector
     fn test_get_bitvec_slice_subslice() {
@@ -1,6 +1,8 @@
 use super::huffman_codes::lichess_huffman_weights;
 use super::score_move::get_move_index;
-use crate::pgn_data::PgnData;
+use crate::compression::header_dict::HeaderDict;
+use crate::compression::utils::{u8_to_bit_vec, varint_to_bit_vec};
+use crate::pgn_data::{PgnData, PgnHeaders};
 use anyhow::{anyhow, Result};
 use bincode::serialize_into;
 use bit_vec::BitVec;
@@ -8,20 +10,13 @@ use flate2::{write::ZlibEncoder, Compression};
 use huffman_compress::Book;
 use shakmaty::{Chess, Move, Position};
 
-/// Converts an i8 to a bit vector of length 8
-fn i8_to_bit_vec(i: i8) -> BitVec {
-    let mut bit_vec = BitVec::new();
-    for j in (0..8).rev() {
-        bit_vec.push((i >> j) & 1 == 1);
-    }
-    bit_vec
-}
-
 /// Game encoder that encodes moves into a bit vector using Huffman encoding
-struct GameEncoder {
+#[derive(Clone)]
+pub(super) struct GameEncoder {
     book: Book<u8>,        // The Huffman book
     pub pos: Chess,        // The current position
     pub bit_moves: BitVec, // The encoded moves
+    pub move_count: usize, // The number of encoded moves
 }
 
 impl GameEncoder {
@@ -32,6 +27,7 @@ impl GameEncoder {
             book,
             pos: Chess::default(),
             bit_moves: BitVec::new(),
+            move_count: 0,
         }
     }
 
@@ -45,6 +41,7 @@ impl GameEncoder {
                 let index: u8 = i.try_into()?;
                 self.book.encode(&mut self.bit_moves, &(index))?;
                 self.pos.play_unchecked(m);
+                self.move_count += 1;
                 Ok(())
             }
             None => Err(anyhow!("Move not found")),
@@ -53,21 +50,23 @@ impl GameEncoder {
 }
 
 /// Compress the headers of a PGN file using ZLib maximum compression
-fn compress_headers(pgn: &PgnData) -> Result<BitVec> {
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+pub(crate) fn compress_headers(headers: &PgnHeaders) -> Result<BitVec> {
     // if the headers are empty, return an empty bit vector
-    if pgn.headers.is_empty() {
+    if headers.is_empty() {
         return Ok(BitVec::new());
     }
 
     // otherwise compress the headers
     let mut compressed_headers = Vec::new();
     let mut encoder = ZlibEncoder::new(&mut compressed_headers, Compression::best());
-    serialize_into(&mut encoder, &pgn.headers)?;
+    serialize_into(&mut encoder, headers)?;
     encoder.finish()?;
     Ok(BitVec::from_bytes(&compressed_headers))
 }
 
 /// Encode the moves of a PGN file using Huffman encoding
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
 fn compress_moves(pgn: &PgnData) -> Result<BitVec> {
     let mut encoder = GameEncoder::new();
     for san_plus in pgn.moves.iter() {
@@ -78,21 +77,50 @@ fn compress_moves(pgn: &PgnData) -> Result<BitVec> {
 }
 
 /// Compress a PGN file
+#[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
 pub fn compress_pgn_data(pgn: &PgnData) -> Result<BitVec> {
-    let mut headers = compress_headers(pgn)?;
-    let mut moves = compress_moves(pgn)?;
+    let headers = compress_headers(&pgn.headers)?;
+    let moves = compress_moves(pgn)?;
+    join_headers_and_moves(headers, moves, pgn.moves.len())
+}
+
+/// Compress a PGN file, encoding the headers against a dictionary shared across a database
+pub fn compress_pgn_data_with_dict(pgn: &PgnData, dict: &HeaderDict) -> Result<BitVec> {
+    let headers = dict.encode(&pgn.headers)?;
+    let moves = compress_moves(pgn)?;
+    join_headers_and_moves(headers, moves, pgn.moves.len())
+}
+
+/// Join compressed headers and moves into a single compressed PGN file.
+/// The number of moves is stored as a varint before the moves, so the decoder reads exactly
+/// that many and ignores any padding after them.
+pub(crate) fn join_headers_and_moves(
+    headers: BitVec,
+    mut moves: BitVec,
+    move_count: usize,
+) -> Result<BitVec> {
+    // add the move count and moves after the header block
+    let mut encoded_pgn = header_block(headers)?;
+    encoded_pgn.append(&mut varint_to_bit_vec(move_count));
+    encoded_pgn.append(&mut moves);
+    Ok(encoded_pgn)
+}
 
-    // if headers are empty, set bitvec to [1], otherwise set to signed i8 (1 byte)
+/// Build the header block that starts a compressed PGN file from the compressed headers
+pub(crate) fn header_block(mut headers: BitVec) -> Result<BitVec> {
+    // if headers are empty, set bitvec to [1], otherwise set to [0] followed by the unsigned header byte length
     let mut encoded_pgn;
     if headers.is_empty() {
         encoded_pgn = BitVec::from_elem(1, true);
     } else {
-        encoded_pgn = i8_to_bit_vec(i8::try_from(headers.to_bytes().len())?);
+        let header_bytes = u8::try_from(headers.to_bytes().len())
+            .map_err(|_| anyhow!("Compressed headers exceed 255 bytes"))?;
+        encoded_pgn = BitVec::from_elem(1, false);
+        encoded_pgn.append(&mut u8_to_bit_vec(header_bytes));
     }
 
-    // add the headers and moves to the encoded pgn
+    // add the headers to the encoded pgn
     encoded_pgn.append(&mut headers);
-    encoded_pgn.append(&mut moves);
     Ok(encoded_pgn)
 }
 
@@ -118,50 +146,11 @@ Qd2 Bb4 29. c3 Be7 30. Nf2 dxc3 31. bxc3 Nd8 32. Bb1 Ne6 33. Nh3 Bc5 34. Ba2 Rd8
 Bxf7+ Kf8 42. Qxf2 Rxd1 43. Bxg6 Qd6 44. g5 Qd3 45. Qc5+ Qd6 46. Qc8+ Kg7 47.
 Qxb7+ Kf8 48. Qf7# 1-0"#;
 
-    #[test]
-    /// Tests that we can convert a 0 i8 to a bit vector
-    fn test_i8_to_bit_vec_0() {
-        let x = 0;
-        let mut expected = BitVec::new();
-        for _ in 0..8 {
-            expected.push(false);
-        }
-        assert_eq!(i8_to_bit_vec(x), expected);
-    }
-
-    #[test]
-    /// Tests that we can convert a 1 i8 to a bit vector
-    fn test_i8_to_bit_vec_1() {
-        let x = 1;
-        let mut expected = BitVec::new();
-        for _ in 0..7 {
-            expected.push(false);
-        }
-        expected.push(true);
-        assert_eq!(i8_to_bit_vec(x), expected);
-    }
-
-    #[test]
-    /// Tests that we can convert a 10 i8 to a bit vector
-    fn test_i8_to_bit_vec_10() {
-        let x = 10;
-        let mut expected = BitVec::new();
-        expected.push(false); // 0
-        expected.push(false); // 0
-        expected.push(false); // 0
-        expected.push(false); // 0
-        expected.push(true); // 1
-        expected.push(false); // 0
-        expected.push(true); // 1
-        expected.push(false); // 0
-        assert_eq!(i8_to_bit_vec(x), expected);
-    }
-
     #[test]
     /// Tests that we can compress the headers of a game
     fn test_compress_headers() {
         let pgn = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
-        let headers = compress_headers(&pgn).unwrap();
+        let headers = compress_headers(&pgn.headers).unwrap();
         assert_eq!(headers.len(), 960);
     }
 
@@ -178,7 +167,7 @@ Qxb7+ Kf8 48. Qf7# 1-0"#;
     fn test_compress_pgn() {
         let pgn = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
         let compressed_pgn = compress_pgn_data(&pgn).unwrap();
-        assert_eq!(compressed_pgn.len(), 1431);
+        assert_eq!(compressed_pgn.len(), 1440);
     }
 
     #[test]
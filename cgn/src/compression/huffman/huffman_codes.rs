@@ -1,7 +1,13 @@
 use huffman_compress::{Book, CodeBuilder, Tree};
 use std::collections::HashMap;
 
+/// Get the Huffman book and tree built from the Lichess move index weights
 pub fn lichess_huffman_weights() -> (Book<u8>, Tree<u8>) {
+    CodeBuilder::from_iter(get_lichess_hashmap()).finish()
+}
+
+/// Get the number of times each move index was played in the Lichess database
+pub fn get_lichess_hashmap() -> HashMap<u8, u32> {
     let mut weights: HashMap<u8, u32> = HashMap::new();
     weights.insert(0, 225_883_932);
     weights.insert(1, 134_956_126);
@@ -259,5 +265,23 @@ pub fn lichess_huffman_weights() -> (Book<u8>, Tree<u8>) {
     weights.insert(253, 0);
     weights.insert(254, 0);
     weights.insert(255, 0);
-    CodeBuilder::from_iter(weights).finish()
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bit_vec::BitVec;
+
+    #[test]
+    /// Tests that indices never seen in the Lichess database still have codes that round trip.
+    fn high_indices_round_trip() {
+        let (book, tree) = lichess_huffman_weights();
+        for index in [0, 120, 200, 255] {
+            let mut bit_vec = BitVec::new();
+            book.encode(&mut bit_vec, &index).unwrap();
+            let decoded: Vec<u8> = tree.decoder(&bit_vec, 1).collect();
+            assert_eq!(decoded, [index]);
+        }
+    }
 }
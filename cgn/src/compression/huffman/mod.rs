@@ -1,20 +1,110 @@
-use crate::{export_to_wasm, pgn_data::PgnData};
-mod decoder;
-mod encoder;
-mod huffman_codes;
-mod score_move;
+use crate::compression::utils::{from_padded_bytes, to_padded_bytes};
+use crate::pgn_data::{PgnData, PgnHeaders};
+mod compressing_visitor;
+pub(crate) mod decoder;
+pub(crate) mod encoder;
+pub(crate) mod huffman_codes;
+pub(crate) mod score_move;
+mod streaming_decoder;
 use bit_vec::BitVec;
-use std::str::FromStr;
-use wasm_bindgen::prelude::*;
 
-pub use decoder::decompress_pgn_data;
-pub use encoder::compress_pgn_data;
+pub use compressing_visitor::CompressingVisitor;
+pub use decoder::{
+    decompress_headers_only, decompress_pgn_data, decompress_pgn_data_with_dict,
+    decompress_pgn_data_with_max_plies,
+};
+pub use encoder::{compress_pgn_data, compress_pgn_data_with_dict};
+pub use streaming_decoder::StreamingDecoder;
 
-export_to_wasm!("huffman", compress_pgn_data, decompress_pgn_data);
+#[cfg(feature = "wasm-huffman")]
+crate::export_to_wasm!("huffman", compress_pgn_data, decompress_pgn_data);
+
+/// Compresses the PGN data into a record padded to a multiple of `align` bytes,
+/// allowing records to be stored and read at fixed strides.
+pub fn compress_pgn_data_padded(pgn_data: &PgnData, align: usize) -> anyhow::Result<Vec<u8>> {
+    to_padded_bytes(&compress_pgn_data(pgn_data)?, align)
+}
+
+/// Decompresses a record created by `compress_pgn_data_padded`, ignoring its padding.
+pub fn decompress_pgn_data_padded(record: &[u8]) -> anyhow::Result<PgnData> {
+    decompress_pgn_data(&from_padded_bytes(record)?)
+}
+
+/// The number of bits at the end of a byte aligned game holding the length of its padding.
+const PAD_LEN_BITS: usize = 3;
+
+/// Compresses the PGN data into whole bytes, so games can be concatenated at byte boundaries
+/// without a container. The bits are followed by zero padding, then the padding length
+/// in the final 3 bits.
+pub fn compress_pgn_data_byte_aligned(pgn_data: &PgnData) -> anyhow::Result<Vec<u8>> {
+    let mut bit_vec = compress_pgn_data(pgn_data)?;
+    let pad = (8 - (bit_vec.len() + PAD_LEN_BITS) % 8) % 8;
+    bit_vec.grow(pad, false);
+    for j in (0..PAD_LEN_BITS).rev() {
+        bit_vec.push((pad >> j) & 1 == 1);
+    }
+    Ok(bit_vec.to_bytes())
+}
+
+/// Decompresses a game created by `compress_pgn_data_byte_aligned`, ignoring its padding.
+pub fn decompress_pgn_data_byte_aligned(bytes: &[u8]) -> anyhow::Result<PgnData> {
+    let mut games = decompress_concatenated_byte_aligned(bytes)?;
+    match (games.pop(), games.is_empty()) {
+        (Some(game), true) => Ok(game),
+        _ => Err(anyhow::anyhow!("Expected a single game")),
+    }
+}
+
+/// Decompresses games created by `compress_pgn_data_byte_aligned` and concatenated, in order.
+/// The end of each game is found by decoding its moves, then skipping its padding to the
+/// next byte boundary, where the padding length stored in its final bits is checked.
+pub fn decompress_concatenated_byte_aligned(bytes: &[u8]) -> anyhow::Result<Vec<PgnData>> {
+    // the bytes are converted once, each game is decoded from the bit it starts at
+    let bit_vec = BitVec::from_bytes(bytes);
+    let mut games = Vec::new();
+    let mut start = 0;
+    while start < bit_vec.len() {
+        let (game, bit_end) = decoder::decompress_pgn_data_at(&bit_vec, start)?;
+
+        // the padding length must match the padding needed to reach the byte boundary
+        let bit_len = bit_end - start;
+        let pad = (8 - (bit_len + PAD_LEN_BITS) % 8) % 8;
+        let end = bit_end + pad + PAD_LEN_BITS;
+        if end > bit_vec.len() {
+            return Err(anyhow::anyhow!("Game is missing its padding length"));
+        }
+        let stored_pad =
+            (end - PAD_LEN_BITS..end).fold(0, |n, i| (n << 1) | usize::from(bit_vec[i]));
+        if stored_pad != pad {
+            return Err(anyhow::anyhow!(
+                "Expected {} bits of padding, found {}",
+                pad,
+                stored_pad
+            ));
+        }
+        games.push(game);
+        start = end;
+    }
+    Ok(games)
+}
+
+/// Replaces the headers of a game compressed by `compress_pgn_data`, copying the move count
+/// and move bits after the header block unchanged, so the moves are never decoded or re-encoded.
+/// Data from the delta_index strategy is also supported, since it stores its headers the same way.
+/// Any padding after the moves in `bytes` is kept.
+pub fn recompress_headers(bytes: &[u8], new_headers: PgnHeaders) -> anyhow::Result<Vec<u8>> {
+    let bit_vec = BitVec::from_bytes(bytes);
+    let (_, headers_end) = decoder::split_headers(&bit_vec, decoder::decompress_headers)?;
+    let mut recompressed = encoder::header_block(encoder::compress_headers(&new_headers)?)?;
+    recompressed.extend(bit_vec.iter().skip(headers_end));
+    Ok(recompressed.to_bytes())
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::compression::header_dict::HeaderDict;
+    use std::str::FromStr;
 
     /// Example PGN string.
     pub const PGN_STR_EXAMPLE: &str = r#"[Event "Titled Tuesday Blitz January 03 Early 2023"]
@@ -37,12 +127,45 @@ Qxb7+ Kf8 48. Qf7# 1-0"#;
     #[test]
     /// Test if the compression is correct for PGN structs.
     fn test_compress_pgn_data() {
-        let pgn_str = PGN_STR_EXAMPLE;
-        let pgn_data = PgnData::from_str(pgn_str).unwrap();
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
         let compressed_data = compress_pgn_data(&pgn_data).unwrap();
         let decompressed_data = decompress_pgn_data(&compressed_data).unwrap();
-        let decompressed_pgn_str = decompressed_data.to_string();
-        assert_eq!(pgn_str, decompressed_pgn_str);
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+    }
+
+    #[test]
+    /// Tests if the compression is correct for headers longer than 127 compressed bytes.
+    fn test_compress_pgn_str_long_headers() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.headers.event = (0..60).map(|i| format!("{} ", i * 7919 % 1009)).collect();
+        let compressed_data = compress_pgn_data(&pgn_data).unwrap();
+        let decompressed_data = decompress_pgn_data(&compressed_data).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+    }
+
+    #[test]
+    /// Tests if a game compressed into a padded record decompresses correctly.
+    fn test_compress_pgn_data_padded() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let record = compress_pgn_data_padded(&pgn_data, 64).unwrap();
+        assert_eq!(record.len() % 64, 0);
+        let decompressed_data = decompress_pgn_data_padded(&record).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+    }
+
+    #[test]
+    /// Tests if compressing with a header dictionary round trips and shrinks the headers.
+    fn test_compress_pgn_data_with_dict() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let mut rematch = pgn_data.clone();
+        rematch.headers.white = pgn_data.headers.black.clone();
+        rematch.headers.black = pgn_data.headers.white.clone();
+        let dict = HeaderDict::train([&pgn_data, &rematch]);
+
+        let compressed_data = compress_pgn_data_with_dict(&pgn_data, &dict).unwrap();
+        let decompressed_data = decompress_pgn_data_with_dict(&compressed_data, &dict).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+        assert!(compressed_data.len() < compress_pgn_data(&pgn_data).unwrap().len());
     }
 
     #[test]
@@ -51,7 +174,149 @@ Qxb7+ Kf8 48. Qf7# 1-0"#;
         let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
         pgn_data.clear_headers();
         let compressed_data = compress_pgn_data(&pgn_data).unwrap();
-        let decompressed_pgn_str = decompress_pgn_data(&compressed_data).unwrap();
-        assert_eq!(pgn_data.to_string(), decompressed_pgn_str.to_string());
+        let decompressed_data = decompress_pgn_data(&compressed_data).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+    }
+
+    #[test]
+    /// Tests if the zero padding added when storing the bits as bytes never decodes
+    /// into phantom trailing moves.
+    fn test_decompress_ignores_padding() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.clear_headers();
+        let moves = std::mem::take(&mut pgn_data.moves);
+
+        // every prefix of the game, so the padding takes every length from 0 to 7 bits
+        let mut unaligned = 0;
+        for san_plus in moves {
+            pgn_data.moves.push(san_plus);
+            let compressed_data = compress_pgn_data(&pgn_data).unwrap();
+            if !compressed_data.len().is_multiple_of(8) {
+                unaligned += 1;
+            }
+            let padded = BitVec::from_bytes(&compressed_data.to_bytes());
+            let decompressed_data = decompress_pgn_data(&padded).unwrap();
+            assert_eq!(decompressed_data.moves.len(), pgn_data.moves.len());
+        }
+        assert!(unaligned > 0);
+    }
+
+    #[test]
+    /// Tests if a game longer than 256 plies round trips.
+    fn test_compress_pgn_data_long_game() {
+        let pgn_str = "1. Nf3 Nf6 2. Ng1 Ng8 ".repeat(100);
+        let pgn_data = PgnData::from_str(&pgn_str).unwrap();
+        assert_eq!(pgn_data.moves.len(), 400);
+        let compressed_data = compress_pgn_data(&pgn_data).unwrap();
+        let decompressed_data = decompress_pgn_data(&compressed_data).unwrap();
+        assert_eq!(decompressed_data.moves.len(), 400);
+    }
+
+    #[test]
+    /// Tests if byte aligned games round trip alone and when concatenated.
+    fn test_compress_pgn_data_byte_aligned() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let mut short_game = PgnData::from_str("1. e4 e5 2. Nf3 *").unwrap();
+        short_game.clear_headers();
+
+        let mut concatenated = Vec::new();
+        for game in [&pgn_data, &short_game, &pgn_data] {
+            let bytes = compress_pgn_data_byte_aligned(game).unwrap();
+            let bit_len = compress_pgn_data(game).unwrap().len();
+            assert_eq!(bytes.len(), (bit_len + PAD_LEN_BITS).div_ceil(8));
+            let decompressed_data = decompress_pgn_data_byte_aligned(&bytes).unwrap();
+            assert_eq!(game.diff(&decompressed_data), []);
+            concatenated.extend(bytes);
+        }
+
+        let games = decompress_concatenated_byte_aligned(&concatenated).unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(pgn_data.diff(&games[0]), []);
+        assert_eq!(short_game.diff(&games[1]), []);
+        assert_eq!(pgn_data.diff(&games[2]), []);
+        assert!(decompress_pgn_data_byte_aligned(&concatenated).is_err());
+
+        // a truncated final game is rejected
+        concatenated.pop();
+        assert!(decompress_concatenated_byte_aligned(&concatenated).is_err());
+    }
+
+    #[test]
+    /// Tests if many concatenated byte aligned games round trip in order.
+    fn test_decompress_many_concatenated_byte_aligned() {
+        let games: Vec<PgnData> = (1..=500)
+            .map(|seed| crate::benchmark_utils::random_game(seed, 40))
+            .collect();
+        let mut concatenated = Vec::new();
+        for game in &games {
+            concatenated.extend(compress_pgn_data_byte_aligned(game).unwrap());
+        }
+
+        let decompressed = decompress_concatenated_byte_aligned(&concatenated).unwrap();
+        assert_eq!(decompressed.len(), games.len());
+        for (game, decompressed_game) in games.iter().zip(&decompressed) {
+            assert_eq!(game.diff(decompressed_game), []);
+        }
+    }
+
+    #[test]
+    /// Tests if replacing the headers keeps the move bits identical.
+    fn test_recompress_headers() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let bytes = compress_pgn_data(&pgn_data).unwrap().to_bytes();
+        let mut headers = pgn_data.headers.clone();
+        headers.black = String::from("Samvel Ter-Sahakyan Jr.");
+        let recompressed = recompress_headers(&bytes, headers.clone()).unwrap();
+
+        // the bits after the header blocks are identical
+        let (_, old_end) =
+            decoder::split_headers(&BitVec::from_bytes(&bytes), decoder::decompress_headers)
+                .unwrap();
+        let (_, new_end) = decoder::split_headers(
+            &BitVec::from_bytes(&recompressed),
+            decoder::decompress_headers,
+        )
+        .unwrap();
+        let old_moves: Vec<bool> = BitVec::from_bytes(&bytes).iter().skip(old_end).collect();
+        let new_moves: Vec<bool> = BitVec::from_bytes(&recompressed)
+            .iter()
+            .skip(new_end)
+            .collect();
+        assert_eq!(new_moves[..old_moves.len()], old_moves);
+
+        let decompressed = decompress_pgn_data(&BitVec::from_bytes(&recompressed)).unwrap();
+        assert_eq!(decompressed.headers.black, "Samvel Ter-Sahakyan Jr.");
+        assert_eq!(pgn_data.moves.len(), decompressed.moves.len());
+        assert_eq!(pgn_data.diff(&decompressed).len(), 1);
+
+        // headers can be removed and added back
+        let removed = recompress_headers(&bytes, PgnHeaders::new()).unwrap();
+        assert!(decompress_pgn_data(&BitVec::from_bytes(&removed))
+            .unwrap()
+            .headers
+            .is_empty());
+        let restored = recompress_headers(&removed, pgn_data.headers.clone()).unwrap();
+        let decompressed = decompress_pgn_data(&BitVec::from_bytes(&restored)).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed), []);
+    }
+
+    #[test]
+    /// Tests if data with fewer move bits than its move count is rejected.
+    fn test_decompress_truncated_moves() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let mut compressed_data = compress_pgn_data(&pgn_data).unwrap();
+        compressed_data.truncate(compressed_data.len() - 20);
+        assert!(decompress_pgn_data(&compressed_data).is_err());
+    }
+
+    #[test]
+    /// Tests if decompression fails once a game exceeds the maximum number of plies.
+    fn test_decompress_pgn_data_with_max_plies() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let compressed_data = compress_pgn_data(&pgn_data).unwrap();
+        let decompressed_data = decompress_pgn_data_with_max_plies(&compressed_data, 95).unwrap();
+        assert_eq!(pgn_data.diff(&decompressed_data), []);
+        assert!(decompress_pgn_data_with_max_plies(&compressed_data, 94).is_err());
+        assert!(decompress_pgn_data_with_max_plies(&compressed_data, 0).is_err());
     }
 }
@@ -9,7 +9,9 @@ pub fn get_move_index(pos: &Chess, m: &Move) -> Option<usize> {
     moves.iter().position(|x| x == m)
 }
 
-/// Generate a list of legal moves for a position sorted by score
+/// Generate a list of legal moves for a position sorted by score.
+/// The moves are always fully legal: shakmaty 0.26 has no public pseudo-legal move generator,
+/// and generating the legal moves takes a small fraction of the time spent ordering them.
 pub fn generate_moves(pos: &Chess) -> Vec<Move> {
     let mut legal_moves = pos.legal_moves();
     legal_moves.sort_unstable_by_key(|m| -move_score(pos, m).unwrap_or(0));
@@ -24,7 +26,7 @@ pub fn move_score(pos: &Chess, m: &Move) -> Result<PieceScore> {
     let move_value = move_pst_score(pos.turn(), m);
 
     let to_value = PieceScore::from(m.to());
-    let from_value = PieceScore::from(m.from().ok_or(anyhow::anyhow!("No from square"))?);
+    let from_value = PieceScore::from(m.from().ok_or_else(|| anyhow::anyhow!("No from square"))?);
 
     Ok((promotion_score << 26)
         + (capture_score << 25)
@@ -91,7 +93,7 @@ fn move_pst_score(turn: Color, m: &Move) -> Result<PieceScore> {
     let to_score = pst_score(m.role().of(turn), m.to())?;
     let from_score = pst_score(
         m.role().of(turn),
-        m.from().ok_or(anyhow::anyhow!("No from square"))?,
+        m.from().ok_or_else(|| anyhow::anyhow!("No from square"))?,
     )?;
     Ok(512 + to_score - from_score)
 }
@@ -324,4 +326,54 @@ mod tests {
         };
         assert_eq!(move_pst_score(pos.turn(), &m).unwrap(), 512 - 5 - 10);
     }
+
+    /// Parses a position from a FEN string.
+    fn pos_from_fen(fen: &str) -> Chess {
+        fen.parse::<shakmaty::fen::Fen>()
+            .unwrap()
+            .into_position(shakmaty::CastlingMode::Standard)
+            .unwrap()
+    }
+
+    /// Returns the SAN of the moves of a position in the order given by `generate_moves`.
+    fn ordered_san(pos: &Chess) -> Vec<String> {
+        generate_moves(pos)
+            .iter()
+            .map(|m| shakmaty::san::San::from_move(pos, m).to_string())
+            .collect()
+    }
+
+    #[test]
+    /// Tests that the starting position follows the Lichess order: by piece square table gain,
+    /// then by destination square, then by origin square.
+    fn lichess_order_starting_position() {
+        let moves = ordered_san(&Chess::default());
+        // e4 gains 52, d4 and Nf3 gain 51 (d4 lands on a higher square), Nc3 gains 50
+        assert_eq!(moves[..4], ["e4", "d4", "Nf3", "Nc3"]);
+        // c3 and f3 lose 20, the most of any move, and c3 lands on the lower square
+        assert_eq!(moves.last().unwrap(), "c3");
+    }
+
+    #[test]
+    /// Tests that moves to a square attacked by an opponent pawn come last, as in Lichess.
+    fn lichess_order_pawn_attacked_squares() {
+        let pos = pos_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+        let moves = ordered_san(&pos);
+        assert_eq!(moves[..3], ["e5", "Nf6", "Nc6"]);
+        // d5 and f5 are both attacked by the pawn on e4
+        assert_eq!(moves[18..], ["d5", "f5"]);
+    }
+
+    #[test]
+    /// Tests that captures come before quiet moves, and promotions before both, as in Lichess.
+    fn lichess_order_captures_and_promotions() {
+        let pos = pos_from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        assert_eq!(ordered_san(&pos)[0], "exd5");
+
+        let pos = pos_from_fen("8/1P6/8/8/8/8/6k1/K7 w - - 0 1");
+        assert_eq!(
+            ordered_san(&pos),
+            ["b8=Q", "b8=R", "b8=B", "b8=N", "Kb1", "Kb2", "Ka2"]
+        );
+    }
 }
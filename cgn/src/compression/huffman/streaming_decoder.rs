@@ -0,0 +1,187 @@
+use super::huffman_codes::lichess_huffman_weights;
+use super::score_move::generate_moves;
+use crate::pgn_data::SanPlusWrapper;
+use anyhow::{anyhow, Result};
+use bit_vec::BitVec;
+use huffman_compress::Tree;
+use shakmaty::{san::SanPlus, Chess};
+use std::cell::Cell;
+
+/// A decoder for Huffman encoded move bits that arrive incrementally, e.g. from a live broadcast.
+/// The bits are the moves as encoded by `compress_pgn_data`, without the header block or
+/// move count. Every fully received move is decoded, and the bits of a partially received
+/// move are kept until the rest of it arrives.
+#[derive(Clone)]
+pub struct StreamingDecoder {
+    tree: Tree<u8>, // The Huffman tree
+    pos: Chess,     // The position after the decoded moves
+    bits: BitVec,   // The received bits not yet decoded
+}
+
+impl StreamingDecoder {
+    /// Creates a new StreamingDecoder starting from the standard starting position.
+    pub fn new() -> StreamingDecoder {
+        StreamingDecoder {
+            tree: lichess_huffman_weights().1,
+            pos: Chess::default(),
+            bits: BitVec::new(),
+        }
+    }
+
+    /// Appends newly received bits.
+    pub fn push_bits(&mut self, more: &BitVec) {
+        self.bits.extend(more.iter());
+    }
+
+    /// Decodes every move whose bits have been fully received, keeping any leftover bits.
+    /// Returns an error if a move index is not legal in the current position, in which case
+    /// the stream is corrupt. The decoder is then left as it was before the call, so no moves
+    /// or bits are lost.
+    pub fn drain_moves(&mut self) -> Result<Vec<SanPlusWrapper>> {
+        let mut pos = self.pos.clone();
+        let mut san_plus_moves = Vec::new();
+
+        // decode one move at a time from a single pass over the bits, counting the bits read
+        let bits_read = Cell::new(0);
+        let mut bits = self
+            .bits
+            .iter()
+            .inspect(|_| bits_read.set(bits_read.get() + 1));
+        let mut consumed = 0;
+        while let Some(index) = self.tree.decoder(bits.by_ref(), 1).next() {
+            consumed = bits_read.get();
+            let moves = generate_moves(&pos);
+            let m = moves
+                .get(usize::from(index))
+                .ok_or_else(|| anyhow!("Failed to decode move"))?;
+            let san_plus = SanPlus::from_move_and_play_unchecked(&mut pos, m);
+            san_plus_moves.push(SanPlusWrapper(san_plus));
+        }
+
+        // commit the decoded moves, keeping the bits of the partially received move
+        self.pos = pos;
+        self.bits = self.bits.iter().skip(consumed).collect();
+        Ok(san_plus_moves)
+    }
+
+    /// Returns the position after the moves decoded so far.
+    pub fn position(&self) -> &Chess {
+        &self.pos
+    }
+}
+
+impl Default for StreamingDecoder {
+    /// Creates a new StreamingDecoder.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::encoder::GameEncoder;
+    use super::*;
+    use crate::pgn_data::PgnData;
+    use std::str::FromStr;
+
+    /// Encodes the moves of a PGN string into Huffman encoded move bits.
+    fn encode_moves(pgn_str: &str) -> (PgnData, BitVec) {
+        let pgn_data = PgnData::from_str(pgn_str).unwrap();
+        let mut encoder = GameEncoder::new();
+        for san_plus in pgn_data.moves.iter() {
+            let m = san_plus.0.san.to_move(&encoder.pos).unwrap();
+            encoder.encode(&m).unwrap();
+        }
+        (pgn_data, encoder.bit_moves)
+    }
+
+    #[test]
+    /// Tests that moves are decoded as their bits arrive in small chunks.
+    fn streaming_decoder_chunks() {
+        let (pgn_data, bits) = encode_moves("1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O *");
+        let mut decoder = StreamingDecoder::new();
+        let mut decoded = Vec::new();
+        let chunks: Vec<bool> = bits.iter().collect();
+        for chunk in chunks.chunks(3) {
+            decoder.push_bits(&chunk.iter().copied().collect());
+            decoded.extend(decoder.drain_moves().unwrap());
+        }
+        let expected: Vec<String> = pgn_data.moves.iter().map(|m| m.0.to_string()).collect();
+        let decoded: Vec<String> = decoded.iter().map(|m| m.0.to_string()).collect();
+        assert_eq!(decoded, expected);
+        assert!(decoder.drain_moves().unwrap().is_empty());
+    }
+
+    #[test]
+    /// Tests that a partially received move is kept until the rest of it arrives.
+    fn streaming_decoder_partial_move() {
+        let (_, bits) = encode_moves("1. a4 *");
+        assert!(bits.len() > 1);
+        let mut decoder = StreamingDecoder::new();
+        let mut first: BitVec = bits.iter().collect();
+        first.truncate(bits.len() - 1);
+        decoder.push_bits(&first);
+        assert!(decoder.drain_moves().unwrap().is_empty());
+        decoder.push_bits(&bits.iter().skip(bits.len() - 1).collect());
+        let moves = decoder.drain_moves().unwrap();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0.to_string(), "a4");
+        assert_eq!(
+            shakmaty::Position::turn(decoder.position()),
+            shakmaty::Color::Black
+        );
+    }
+
+    #[test]
+    /// Tests that a corrupt move leaves the decoder as it was, keeping the buffered bits.
+    fn streaming_decoder_corrupt_move() {
+        let (_, mut bits) = encode_moves("1. e4 e5 *");
+        // no position in this game has 200 legal moves
+        let (book, _) = lichess_huffman_weights();
+        book.encode(&mut bits, &200).unwrap();
+
+        let mut decoder = StreamingDecoder::new();
+        decoder.push_bits(&bits);
+        assert!(decoder.drain_moves().is_err());
+        assert_eq!(decoder.position(), &Chess::default());
+        assert_eq!(decoder.bits, bits);
+        assert!(decoder.drain_moves().is_err());
+    }
+
+    #[test]
+    /// Tests that a long stream is drained in a single pass.
+    fn streaming_decoder_long_stream() {
+        let game = crate::benchmark_utils::random_game(7, 400);
+        let mut encoder = GameEncoder::new();
+        for san_plus in game.moves.iter() {
+            let m = san_plus.0.san.to_move(&encoder.pos).unwrap();
+            encoder.encode(&m).unwrap();
+        }
+        let mut decoder = StreamingDecoder::new();
+        decoder.push_bits(&encoder.bit_moves);
+        assert_eq!(decoder.drain_moves().unwrap().len(), game.moves.len());
+        assert!(decoder.bits.is_empty());
+    }
+
+    #[test]
+    /// Tests that a cloned decoder continues independently from the same position.
+    fn streaming_decoder_clone() {
+        let (_, prefix) = encode_moves("1. e4 e5 *");
+        let mut decoder = StreamingDecoder::new();
+        decoder.push_bits(&prefix);
+        assert_eq!(decoder.drain_moves().unwrap().len(), 2);
+
+        // fork at the branch point and feed each decoder a different continuation
+        let mut fork = decoder.clone();
+        for (decoder, pgn_str, expected) in [
+            (&mut decoder, "1. e4 e5 2. Nf3 *", "Nf3"),
+            (&mut fork, "1. e4 e5 2. Bc4 *", "Bc4"),
+        ] {
+            let (_, bits) = encode_moves(pgn_str);
+            decoder.push_bits(&bits.iter().skip(prefix.len()).collect());
+            let moves = decoder.drain_moves().unwrap();
+            assert_eq!(moves.len(), 1);
+            assert_eq!(moves[0].0.to_string(), expected);
+        }
+    }
+}
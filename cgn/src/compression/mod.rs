@@ -1,35 +1,66 @@
 // Put all compression modules here for export to root lib.rs.
 pub mod bincode;
+pub mod bincode_compact;
+pub mod db;
+pub mod delta_index;
+pub mod header_dict;
 pub mod huffman;
+pub mod move_order;
+pub mod nags;
+pub mod utils;
+pub mod wasm_error;
 
 /// Accepts a module that contains the following and exports them to WASM string versions.
 /// * compress_pgn_data(&PgnData) -> Result<BitVec>.
 /// * decompress_pgn_data(&BitVec) -> Result<PgnData>.
+///
+/// Each invocation is gated behind the `wasm-<strategy>` feature of its strategy.
+/// Failures return an empty buffer, and record an error for `cgn_last_error_code` and
+/// `cgn_last_error_message`.
 #[macro_export]
 macro_rules! export_to_wasm {
     ($module_name:literal, $compress_pgn_data:ident, $decompress_pgn_data:ident) => {
         ::paste::paste! {
             /// Compresses a PGN string into a vector of bytes.
-            #[wasm_bindgen]
+            #[::wasm_bindgen::prelude::wasm_bindgen]
             pub fn [<$module_name _compress_pgn_str>](pgn_str: &str) -> Vec<u8> {
+                use $crate::compression::wasm_error::{set_last_error, CgnErrorCode};
+
                 // if pgn_data is invalid, return an empty vector
-                let pgn_data = match PgnData::from_str(pgn_str) {
+                let pgn_data = match <$crate::pgn_data::PgnData as ::std::str::FromStr>::from_str(pgn_str) {
                     Ok(pgn_data) => pgn_data,
-                    Err(_) => return Vec::new(),
+                    Err(e) => {
+                        set_last_error(CgnErrorCode::InvalidPgn, e.to_string());
+                        return Vec::new();
+                    }
                 };
 
                 // compress the data and return the result
                 match $compress_pgn_data(&pgn_data) {
-                    Ok(compressed_data) => compressed_data.to_bytes(),
-                    Err(_) => Vec::new(),
+                    Ok(compressed_data) => {
+                        set_last_error(CgnErrorCode::None, String::new());
+                        compressed_data.to_bytes()
+                    }
+                    Err(e) => {
+                        set_last_error(CgnErrorCode::CompressionFailed, e.to_string());
+                        Vec::new()
+                    }
                 }
             }
             /// Decompresses a vector of bytes into a PGN string.
-            #[wasm_bindgen]
+            #[::wasm_bindgen::prelude::wasm_bindgen]
             pub fn [<$module_name _decompress_pgn_str>](compressed_data: &[u8]) -> String {
-                match $decompress_pgn_data(&BitVec::from_bytes(compressed_data)) {
-                    Ok(pgn_data) => pgn_data.to_string(),
-                    Err(_) => String::new(),
+                use $crate::compression::wasm_error::{set_last_error, CgnErrorCode};
+
+                match $decompress_pgn_data(&::bit_vec::BitVec::from_bytes(compressed_data)) {
+                    Ok(pgn_data) => {
+                        set_last_error(CgnErrorCode::None, String::new());
+                        pgn_data.to_string()
+                    }
+                    Err(e) => {
+                        set_last_error(CgnErrorCode::DecompressionFailed, e.to_string());
+                        String::new()
+                    }
                 }
             }
         }
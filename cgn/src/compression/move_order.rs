@@ -0,0 +1,48 @@
+use crate::compression::huffman::score_move::generate_moves;
+use anyhow::Result;
+use shakmaty::{fen::Fen, CastlingMode, Chess};
+
+/// Returns the legal moves of the position given as a FEN in UCI, each with its index
+/// in the order used by the move index strategies (huffman, delta_index and bincode_compact).
+/// This is the list the decoder picks from at each ply, so it shows why a game decodes differently.
+pub fn ordered_moves_for_fen(fen: &str) -> Result<Vec<(String, usize)>> {
+    let pos: Chess = fen.parse::<Fen>()?.into_position(CastlingMode::Standard)?;
+    Ok(generate_moves(&pos)
+        .iter()
+        .enumerate()
+        .map(|(index, m)| (m.to_uci(CastlingMode::Standard).to_string(), index))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::huffman::score_move::get_move_index;
+    use shakmaty::{uci::Uci, Position};
+
+    #[test]
+    /// Tests if every legal move is listed once, with the index the encoder gives it.
+    fn ordered_moves_match_move_index() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let pos: Chess = fen
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+        let moves = ordered_moves_for_fen(fen).unwrap();
+        assert_eq!(moves.len(), pos.legal_moves().len());
+        for (i, (uci, index)) in moves.iter().enumerate() {
+            assert_eq!(*index, i);
+            let m = uci.parse::<Uci>().unwrap().to_move(&pos).unwrap();
+            assert_eq!(get_move_index(&pos, &m), Some(*index));
+        }
+    }
+
+    #[test]
+    /// Tests if castling is given as the king's move and invalid FENs are rejected.
+    fn ordered_moves_castling_and_invalid_fen() {
+        let moves = ordered_moves_for_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(moves.iter().any(|(uci, _)| uci == "e1g1"));
+        assert!(ordered_moves_for_fen("not a fen").is_err());
+    }
+}
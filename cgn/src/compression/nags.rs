@@ -0,0 +1,110 @@
+use crate::compression::utils::{
+    bit_vec_to_u8, bit_vec_to_varint, u8_to_bit_vec, varint_to_bit_vec,
+};
+use anyhow::{anyhow, Result};
+use bit_vec::BitVec;
+
+/// Compresses the numeric annotation glyphs (NAGs) of each move of a game, as returned by
+/// `PgnData::from_str_with_nags`, into a stream stored alongside the game's compressed data.
+/// Most moves have no NAGs, so only the annotated moves are stored:
+/// * The number of annotated moves as a varint.
+/// * For each annotated move, the number of unannotated moves since the last annotated move
+///   as a varint, the number of NAGs as a varint, and each NAG as 8 bits.
+///
+/// A game without NAGs takes 8 bits.
+pub fn compress_nags(nags: &[Vec<u8>]) -> BitVec {
+    let annotated: Vec<(usize, &Vec<u8>)> = nags
+        .iter()
+        .enumerate()
+        .filter(|(_, nags)| !nags.is_empty())
+        .collect();
+
+    let mut bit_vec = varint_to_bit_vec(annotated.len());
+    let mut next_ply = 0;
+    for (ply, nags) in annotated {
+        bit_vec.append(&mut varint_to_bit_vec(ply - next_ply));
+        bit_vec.append(&mut varint_to_bit_vec(nags.len()));
+        for nag in nags {
+            bit_vec.append(&mut u8_to_bit_vec(*nag));
+        }
+        next_ply = ply + 1;
+    }
+    bit_vec
+}
+
+/// Decompresses the NAGs of each of the `move_count` moves of a game from a stream created by
+/// `compress_nags`, returning an error if they don't fit in the game.
+pub fn decompress_nags(bit_vec: &BitVec, move_count: usize) -> Result<Vec<Vec<u8>>> {
+    let mut nags = vec![Vec::new(); move_count];
+    let (annotated, mut pos) = bit_vec_to_varint(bit_vec, 0)?;
+    let mut next_ply = 0;
+    for _ in 0..annotated {
+        let (gap, bits) = bit_vec_to_varint(bit_vec, pos)?;
+        pos += bits;
+        let (count, bits) = bit_vec_to_varint(bit_vec, pos)?;
+        pos += bits;
+
+        let ply = next_ply + gap;
+        let move_nags = nags
+            .get_mut(ply)
+            .ok_or_else(|| anyhow!("NAGs of ply {} are beyond the end of the game", ply + 1))?;
+        for _ in 0..count {
+            if pos + 8 > bit_vec.len() {
+                return Err(anyhow!("NAGs are truncated"));
+            }
+            move_nags.push(bit_vec_to_u8(&bit_vec.iter().skip(pos).take(8).collect())?);
+            pos += 8;
+        }
+        next_ply = ply + 1;
+    }
+    Ok(nags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn_data::PgnData;
+
+    /// Example annotated PGN string.
+    const PGN_STR_NAGS: &str = r#"[Event ""]
+[Site ""]
+[Date ""]
+[Round ""]
+[White ""]
+[Black ""]
+[Result "1-0"]
+
+1. e4 $1 e5 2. Qh5 $2 $10 Nc6 3. Bc4 Nf6 $4 4. Qxf7# $1 1-0"#;
+
+    #[test]
+    /// Tests that NAGs are parsed per move, compressed sparsely and written back after each move.
+    fn nags_round_trip() {
+        let (pgn_data, nags) = PgnData::from_str_with_nags(PGN_STR_NAGS).unwrap();
+        assert_eq!(nags.len(), pgn_data.moves.len());
+        assert_eq!(nags[2], [2, 10]);
+        assert!(nags[1].is_empty());
+
+        let compressed = compress_nags(&nags);
+        assert_eq!(compressed.len(), 8 + 4 * 16 + 5 * 8);
+        let decompressed = decompress_nags(&compressed, pgn_data.moves.len()).unwrap();
+        assert_eq!(decompressed, nags);
+        assert_eq!(pgn_data.to_string_with_nags(&decompressed), PGN_STR_NAGS);
+        assert!(!pgn_data.to_string().contains('$'));
+    }
+
+    #[test]
+    /// Tests that a game without NAGs takes a single byte, and that invalid streams are rejected.
+    fn nags_sparse_and_invalid() {
+        let nags = vec![Vec::new(); 40];
+        let compressed = compress_nags(&nags);
+        assert_eq!(compressed.len(), 8);
+        assert_eq!(decompress_nags(&compressed, 40).unwrap(), nags);
+
+        // NAGs after the last move, and a truncated stream
+        let compressed = compress_nags(&[vec![], vec![], vec![3]]);
+        assert!(decompress_nags(&compressed, 2).is_err());
+        let mut truncated = compressed.clone();
+        truncated.truncate(compressed.len() - 1);
+        assert!(decompress_nags(&truncated, 3).is_err());
+    }
+}
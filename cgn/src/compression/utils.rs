@@ -0,0 +1,185 @@
+use anyhow::{anyhow, Result};
+use bit_vec::BitVec;
+
+pub mod bit_stats;
+pub mod entropy;
+
+/// Converts a u8 to a bit vector of length 8 (most significant bit first)
+pub fn u8_to_bit_vec(i: u8) -> BitVec {
+    let mut bit_vec = BitVec::new();
+    for j in (0..8).rev() {
+        bit_vec.push((i >> j) & 1 == 1);
+    }
+    bit_vec
+}
+
+/// Converts a bit vector of length 8 (most significant bit first) to a u8
+pub fn bit_vec_to_u8(bit_vec: &BitVec) -> Result<u8> {
+    if bit_vec.len() != 8 {
+        return Err(anyhow!("Expected 8 bits, got {}", bit_vec.len()));
+    }
+    Ok(bit_vec
+        .iter()
+        .fold(0, |byte, bit| (byte << 1) | u8::from(bit)))
+}
+
+/// Converts an unsigned integer to a LEB128 varint bit vector: 8 bits per group of 7 value bits,
+/// least significant group first, with the first bit of each group set if more groups follow
+pub fn varint_to_bit_vec(mut i: usize) -> BitVec {
+    let mut bit_vec = BitVec::new();
+    loop {
+        let group = (i & 0x7F) as u8;
+        i >>= 7;
+        if i == 0 {
+            bit_vec.append(&mut u8_to_bit_vec(group));
+            return bit_vec;
+        }
+        bit_vec.append(&mut u8_to_bit_vec(group | 0x80));
+    }
+}
+
+/// Reads a varint written by `varint_to_bit_vec` starting at bit `start`,
+/// returning the value and the number of bits read
+pub fn bit_vec_to_varint(bit_vec: &BitVec, start: usize) -> Result<(usize, usize)> {
+    let mut value = 0;
+    let mut shift = 0;
+    let mut pos = start;
+    loop {
+        if pos + 8 > bit_vec.len() {
+            return Err(anyhow!("Varint is truncated"));
+        }
+        let group = (pos..pos + 8).fold(0u8, |byte, i| (byte << 1) | u8::from(bit_vec[i]));
+        pos += 8;
+        let bits = usize::from(group & 0x7F);
+        if shift >= usize::BITS || (bits << shift) >> shift != bits {
+            return Err(anyhow!("Varint overflows"));
+        }
+        value |= bits << shift;
+        shift += 7;
+        if group & 0x80 == 0 {
+            return Ok((value, pos - start));
+        }
+    }
+}
+
+/// Converts a bit vector into a fixed-width record of bytes.
+/// The record starts with the bit length as a big-endian u16, followed by the bits,
+/// zero padded so the record length is a multiple of `align` bytes.
+pub fn to_padded_bytes(bit_vec: &BitVec, align: usize) -> Result<Vec<u8>> {
+    if align == 0 {
+        return Err(anyhow!("Alignment must be greater than 0"));
+    }
+    let bit_len = u16::try_from(bit_vec.len())
+        .map_err(|_| anyhow!("Bit vector exceeds {} bits", u16::MAX))?;
+
+    // write the length prefix and the bits, then pad to the next multiple of align
+    let mut bytes = bit_len.to_be_bytes().to_vec();
+    bytes.extend(bit_vec.to_bytes());
+    let padded_len = bytes.len().div_ceil(align) * align;
+    bytes.resize(padded_len, 0);
+    Ok(bytes)
+}
+
+/// Converts a fixed-width record of bytes created by `to_padded_bytes` back into a bit vector.
+/// The padding is ignored.
+pub fn from_padded_bytes(bytes: &[u8]) -> Result<BitVec> {
+    if bytes.len() < 2 {
+        return Err(anyhow!("Record is missing its length prefix"));
+    }
+    let bit_len = usize::from(u16::from_be_bytes([bytes[0], bytes[1]]));
+    let mut bit_vec = BitVec::from_bytes(&bytes[2..]);
+    if bit_vec.len() < bit_len {
+        return Err(anyhow!("Record is shorter than its length prefix"));
+    }
+    bit_vec.truncate(bit_len);
+    Ok(bit_vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Tests that we can convert a 0 u8 to a bit vector
+    fn test_u8_to_bit_vec_0() {
+        assert_eq!(u8_to_bit_vec(0), BitVec::from_elem(8, false));
+    }
+
+    #[test]
+    /// Tests that we can convert a 10 u8 to a bit vector
+    fn test_u8_to_bit_vec_10() {
+        assert_eq!(u8_to_bit_vec(10), BitVec::from_bytes(&[0b0000_1010]));
+    }
+
+    #[test]
+    /// Tests that values above 127 are stored unsigned
+    fn test_u8_to_bit_vec_200() {
+        assert_eq!(u8_to_bit_vec(200), BitVec::from_bytes(&[200]));
+    }
+
+    #[test]
+    /// Tests that every u8 round trips through a bit vector
+    fn test_u8_round_trip() {
+        for i in 0..=u8::MAX {
+            assert_eq!(bit_vec_to_u8(&u8_to_bit_vec(i)).unwrap(), i);
+        }
+    }
+
+    #[test]
+    /// Tests that a bit vector of the wrong length is rejected
+    fn test_bit_vec_to_u8_wrong_length() {
+        assert!(bit_vec_to_u8(&BitVec::from_elem(3, true)).is_err());
+    }
+
+    #[test]
+    /// Tests that varints round trip and use one group per 7 bits of the value
+    fn test_varint_round_trip() {
+        for (i, bits) in [(0, 8), (1, 8), (127, 8), (128, 16), (300, 16), (16_384, 24)] {
+            let bit_vec = varint_to_bit_vec(i);
+            assert_eq!(bit_vec.len(), bits);
+            assert_eq!(bit_vec_to_varint(&bit_vec, 0).unwrap(), (i, bits));
+        }
+        assert_eq!(
+            varint_to_bit_vec(300),
+            BitVec::from_bytes(&[0b1010_1100, 0b0000_0010])
+        );
+
+        // reading from an offset ignores the bits before and after the varint
+        let mut bit_vec = BitVec::from_elem(3, true);
+        bit_vec.append(&mut varint_to_bit_vec(usize::MAX));
+        bit_vec.push(true);
+        assert_eq!(bit_vec_to_varint(&bit_vec, 3).unwrap().0, usize::MAX);
+    }
+
+    #[test]
+    /// Tests that truncated and overflowing varints are rejected
+    fn test_varint_invalid() {
+        assert!(bit_vec_to_varint(&BitVec::from_elem(7, false), 0).is_err());
+        assert!(bit_vec_to_varint(&BitVec::from_bytes(&[0x80]), 0).is_err());
+        assert!(bit_vec_to_varint(&BitVec::from_bytes(&[0xFF; 11]), 0).is_err());
+    }
+
+    #[test]
+    /// Tests that a padded record is a multiple of the alignment and round trips
+    fn test_padded_bytes_round_trip() {
+        let mut bit_vec = BitVec::from_bytes(&[0b1010_1010, 0b1100_0000]);
+        bit_vec.truncate(11);
+        let bytes = to_padded_bytes(&bit_vec, 64).unwrap();
+        assert_eq!(bytes.len(), 64);
+        assert_eq!(&bytes[..2], &[0, 11]);
+        assert_eq!(from_padded_bytes(&bytes).unwrap(), bit_vec);
+    }
+
+    #[test]
+    /// Tests that a zero alignment is rejected
+    fn test_padded_bytes_zero_align() {
+        assert!(to_padded_bytes(&BitVec::new(), 0).is_err());
+    }
+
+    #[test]
+    /// Tests that a record shorter than its length prefix is rejected
+    fn test_padded_bytes_truncated() {
+        assert!(from_padded_bytes(&[0]).is_err());
+        assert!(from_padded_bytes(&[0, 9, 0xFF]).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::compression::huffman::huffman_codes::lichess_huffman_weights;
+use crate::compression::huffman::score_move::get_move_index;
+use crate::pgn_data::PgnData;
+use anyhow::{anyhow, Result};
+
+/// Returns the length in bits of the Huffman code of each move of the game, as encoded
+/// by the huffman strategy. The lengths exclude the headers and move count, so their sum
+/// is the size of the move bits. Long codes show where the static Lichess codebook
+/// fits the game poorly, e.g. king moves in endgames.
+pub fn bit_costs(pgn: &PgnData) -> Result<Vec<usize>> {
+    let (book, _) = lichess_huffman_weights();
+    let positions = pgn.replay()?;
+    let mut costs = Vec::with_capacity(pgn.moves.len());
+    for (pos, san_plus) in positions.iter().zip(&pgn.moves) {
+        let m = san_plus.0.san.to_move(pos)?;
+        let index = get_move_index(pos, &m).ok_or_else(|| anyhow!("Move not found"))?;
+        let code = book
+            .get(&u8::try_from(index)?)
+            .ok_or_else(|| anyhow!("Move index has no Huffman code"))?;
+        costs.push(code.len());
+    }
+    Ok(costs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::huffman::compress_pgn_data;
+    use std::str::FromStr;
+
+    #[test]
+    /// Tests that the costs sum to the size of the move bits.
+    fn bit_costs_sum_to_move_bits() {
+        let mut pgn =
+            PgnData::from_str("1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O *").unwrap();
+        pgn.clear_headers();
+        let costs = bit_costs(&pgn).unwrap();
+        assert_eq!(costs.len(), 9);
+        assert!(costs.iter().all(|cost| *cost > 0));
+
+        // the compressed game is a flag bit and an 8 bit move count, followed by the moves
+        let compressed = compress_pgn_data(&pgn).unwrap();
+        assert_eq!(costs.iter().sum::<usize>(), compressed.len() - 9);
+    }
+
+    #[test]
+    /// Tests that an illegal move is rejected.
+    fn bit_costs_illegal_move() {
+        let pgn = PgnData::from_str("1. e4 e4 *").unwrap();
+        assert!(bit_costs(&pgn).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::compression::huffman::score_move::get_move_index;
+use crate::pgn_data::PgnData;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// Returns the theoretical minimum size in bits of the moves of the game, when each move index
+/// is encoded independently with the distribution given by `weights`, e.g. `get_lichess_hashmap`.
+/// This is the sum of `-log2(p(index))` over the moves. Comparing it to the size of the move
+/// bits of a strategy using the same weights shows how much it loses to whole bit codes.
+/// Returns an error if a move index has a weight of 0, as it can't be encoded at all.
+pub fn entropy_bits(pgn: &PgnData, weights: &HashMap<u8, u32>) -> Result<f64> {
+    let total = weights.values().map(|w| f64::from(*w)).sum::<f64>();
+    let positions = pgn.replay()?;
+    let mut bits = 0.0;
+    for (pos, san_plus) in positions.iter().zip(&pgn.moves) {
+        let m = san_plus.0.san.to_move(pos)?;
+        let index = get_move_index(pos, &m).ok_or_else(|| anyhow!("Move not found"))?;
+        let weight = weights.get(&u8::try_from(index)?).copied().unwrap_or(0);
+        if weight == 0 {
+            return Err(anyhow!("Move index {} has a weight of 0", index));
+        }
+        bits -= (f64::from(weight) / total).log2();
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::huffman::huffman_codes::get_lichess_hashmap;
+    use std::str::FromStr;
+
+    #[test]
+    /// Tests that each move costs the information content of its index under the Lichess weights.
+    fn entropy_bits_lichess() {
+        // each of these moves is the first in the move order
+        let pgn = PgnData::from_str("1. e4 e5 2. Nf3 *").unwrap();
+        let weights = get_lichess_hashmap();
+        let total = weights.values().map(|w| f64::from(*w)).sum::<f64>();
+        let expected = -3.0 * (f64::from(weights[&0]) / total).log2();
+        assert!((entropy_bits(&pgn, &weights).unwrap() - expected).abs() < 1e-9);
+
+        // a rarely played move costs more than the most common one
+        let rare = PgnData::from_str("1. a4 *").unwrap();
+        assert!(entropy_bits(&rare, &weights).unwrap() > expected / 3.0);
+    }
+
+    #[test]
+    /// Tests that uniform weights give 8 bits per move, and that unencodable moves are rejected.
+    fn entropy_bits_uniform_and_zero_weights() {
+        let pgn = PgnData::from_str("1. e4 e5 2. Nf3 *").unwrap();
+        let uniform: HashMap<u8, u32> = (0..=255).map(|index| (index, 1)).collect();
+        assert!((entropy_bits(&pgn, &uniform).unwrap() - 24.0).abs() < 1e-9);
+        assert_eq!(entropy_bits(&PgnData::new(), &uniform).unwrap(), 0.0);
+
+        let only_first: HashMap<u8, u32> = HashMap::from([(0, 1)]);
+        assert_eq!(entropy_bits(&pgn, &only_first).unwrap(), 0.0);
+        let pgn = PgnData::from_str("1. a4 *").unwrap();
+        assert!(entropy_bits(&pgn, &only_first).is_err());
+    }
+}
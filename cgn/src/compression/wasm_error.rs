@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// The error codes of the WASM exports, which return an empty buffer on failure.
+/// The codes are stable, so non-Rust callers can match on them.
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CgnErrorCode {
+    /// The last call succeeded.
+    None = 0,
+    /// The PGN string could not be parsed.
+    InvalidPgn = 1,
+    /// The game could not be compressed, e.g. it has an illegal move.
+    CompressionFailed = 2,
+    /// The compressed data could not be decompressed, e.g. it is truncated or corrupt.
+    DecompressionFailed = 3,
+}
+
+thread_local! {
+    /// The code and message of the error of the last call to a WASM export on this thread.
+    static LAST_ERROR: RefCell<(CgnErrorCode, String)> =
+        const { RefCell::new((CgnErrorCode::None, String::new())) };
+}
+
+/// Records the error of a WASM export, or clears it when given `CgnErrorCode::None`.
+#[doc(hidden)]
+pub fn set_last_error(code: CgnErrorCode, message: String) {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = (code, message));
+}
+
+/// Returns the error code of the last call to a WASM export on this thread.
+#[wasm_bindgen]
+pub fn cgn_last_error_code() -> CgnErrorCode {
+    LAST_ERROR.with(|last_error| last_error.borrow().0)
+}
+
+/// Returns the error message of the last call to a WASM export on this thread,
+/// or an empty string if it succeeded.
+#[wasm_bindgen]
+pub fn cgn_last_error_message() -> String {
+    LAST_ERROR.with(|last_error| last_error.borrow().1.clone())
+}
+
+#[cfg(all(test, feature = "wasm-huffman"))]
+mod tests {
+    use super::*;
+    use crate::compression::huffman::{huffman_compress_pgn_str, huffman_decompress_pgn_str};
+
+    #[test]
+    /// Tests that the WASM exports record their errors and clear them on success.
+    fn last_error() {
+        let compressed = huffman_compress_pgn_str("1. e4 e5 *");
+        assert!(!compressed.is_empty());
+        assert_eq!(cgn_last_error_code(), CgnErrorCode::None);
+        assert!(cgn_last_error_message().is_empty());
+
+        assert!(huffman_compress_pgn_str("1. e4 e5 2. Ke3 *").is_empty());
+        assert_eq!(cgn_last_error_code(), CgnErrorCode::CompressionFailed);
+        assert!(!cgn_last_error_message().is_empty());
+
+        assert!(huffman_decompress_pgn_str(&[]).is_empty());
+        assert_eq!(cgn_last_error_code(), CgnErrorCode::DecompressionFailed);
+        assert_eq!(cgn_last_error_message(), "Compressed data is empty");
+
+        assert!(!huffman_decompress_pgn_str(&compressed).is_empty());
+        assert_eq!(cgn_last_error_code(), CgnErrorCode::None);
+    }
+}
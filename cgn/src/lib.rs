@@ -1,2 +1,4 @@
+pub mod archive;
+pub mod benchmark_utils;
 pub mod compression;
 pub mod pgn_data;
@@ -0,0 +1,138 @@
+use super::PgnData;
+use crate::compression::huffman::score_move::get_move_index;
+use shakmaty::{fen::Fen, Chess, EnPassantMode, Position};
+
+/// FNV-1a offset basis and prime for 64-bit hashes.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A 64-bit FNV-1a hasher, used instead of `DefaultHasher` because its output is stable
+/// across Rust versions and platforms.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    /// Hashes the given bytes.
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Hashes the given string followed by a 0 byte, so adjacent strings can't run together.
+    fn write_str(&mut self, s: &str) {
+        self.write(s.as_bytes());
+        self.write(&[0]);
+    }
+}
+
+impl PgnData {
+    /// Returns a stable identifier of the moves of the game, ignoring the headers.
+    /// Games with the same moves have the same identifier, however their SAN is written.
+    ///
+    /// The FNV-1a hash is taken over:
+    /// * The FEN of the starting position followed by a 0 byte.
+    /// * The index of each move among the legal moves, as ordered by the huffman strategy,
+    ///   as a little-endian u32.
+    /// * If a move is illegal, a 0xFFFFFFFF marker followed by the SAN of it and every later
+    ///   move, each followed by a 0 byte.
+    pub fn content_id(&self) -> u64 {
+        let mut hasher = Fnv1a(FNV_OFFSET);
+        self.hash_moves(&mut hasher);
+        hasher.0
+    }
+
+    /// Returns a stable identifier of the moves and headers of the game.
+    /// The FNV-1a hash is taken over the same data as `content_id`, followed by the seven
+    /// header values in PGN order, each followed by a 0 byte.
+    pub fn content_id_with_headers(&self) -> u64 {
+        let mut hasher = Fnv1a(FNV_OFFSET);
+        self.hash_moves(&mut hasher);
+        for value in [
+            &self.headers.event,
+            &self.headers.site,
+            &self.headers.date,
+            &self.headers.round,
+            &self.headers.white,
+            &self.headers.black,
+            &self.headers.result,
+        ] {
+            hasher.write_str(value);
+        }
+        hasher.0
+    }
+
+    /// Hashes the starting position and the moves of the game.
+    fn hash_moves(&self, hasher: &mut Fnv1a) {
+        let mut pos = Chess::default();
+        hasher.write_str(&Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string());
+
+        for (i, san_plus) in self.moves.iter().enumerate() {
+            let index = san_plus
+                .0
+                .san
+                .to_move(&pos)
+                .ok()
+                .and_then(|m| get_move_index(&pos, &m).map(|index| (m, index)));
+            match index {
+                Some((m, index)) => {
+                    hasher.write(&(index as u32).to_le_bytes());
+                    pos.play_unchecked(&m);
+                }
+                // the game can't be replayed past an illegal move, so hash the rest as SAN
+                None => {
+                    hasher.write(&u32::MAX.to_le_bytes());
+                    for san_plus in &self.moves[i..] {
+                        hasher.write_str(&san_plus.0.to_string());
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    /// Tests that the identifier ignores the headers and how the SAN is written.
+    fn content_id_ignores_headers_and_san_style() {
+        let a = PgnData::from_str("[White \"A\"]\n\n1. Nf3 Nf6 2. Nc3 Nc6 *").unwrap();
+        let b = PgnData::from_str("[White \"B\"]\n\n1. Ngf3 Ng8f6 2. Nbc3 Nbc6 1-0").unwrap();
+        assert_eq!(a.content_id(), b.content_id());
+        assert_ne!(a.content_id_with_headers(), b.content_id_with_headers());
+    }
+
+    #[test]
+    /// Tests that different moves give different identifiers.
+    fn content_id_differs_by_moves() {
+        let a = PgnData::from_str("1. e4 e5 *").unwrap();
+        let b = PgnData::from_str("1. e4 c5 *").unwrap();
+        let c = PgnData::from_str("1. e4 *").unwrap();
+        assert_ne!(a.content_id(), b.content_id());
+        assert_ne!(a.content_id(), c.content_id());
+    }
+
+    #[test]
+    /// Tests that the identifier is stable, so it can be stored.
+    fn content_id_is_stable() {
+        let empty = PgnData::new();
+        let mut hasher = Fnv1a(FNV_OFFSET);
+        hasher.write_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(empty.content_id(), hasher.0);
+        let mut hasher = Fnv1a(FNV_OFFSET);
+        hasher.write(b"a");
+        assert_eq!(hasher.0, 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    /// Tests that games with illegal moves still get an identifier.
+    fn content_id_illegal_move() {
+        let a = PgnData::from_str("1. e4 e4 *").unwrap();
+        let b = PgnData::from_str("1. e4 d4 *").unwrap();
+        assert_ne!(a.content_id(), b.content_id());
+    }
+}
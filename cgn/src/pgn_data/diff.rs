@@ -0,0 +1,128 @@
+use super::PgnData;
+
+/// A difference between two games, as found by `PgnData::diff`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Difference {
+    /// A header of the seven tag roster has different values.
+    Header {
+        tag: &'static str,
+        expected: String,
+        actual: String,
+    },
+    /// The games differ at the given 1-based ply, holding the SAN of each game's move,
+    /// or `None` if that game has already ended.
+    Move {
+        ply: usize,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+}
+
+impl std::fmt::Display for Difference {
+    /// Formats the difference, e.g. `ply 37: expected Nf4+, got Ng6+`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difference::Header {
+                tag,
+                expected,
+                actual,
+            } => write!(f, "{}: expected \"{}\", got \"{}\"", tag, expected, actual),
+            Difference::Move {
+                ply,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "ply {}: expected {}, got {}",
+                ply,
+                expected.as_deref().unwrap_or("end of game"),
+                actual.as_deref().unwrap_or("end of game")
+            ),
+        }
+    }
+}
+
+impl PgnData {
+    /// Returns the differences between this game (the expected one) and another game:
+    /// every mismatched header, followed by the first ply at which the moves differ.
+    /// Moves are compared by their SAN, so `Ngf3` and `Nf3` differ.
+    /// An empty list means the games are equal.
+    pub fn diff(&self, other: &PgnData) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        for (tag, expected, actual) in [
+            ("Event", &self.headers.event, &other.headers.event),
+            ("Site", &self.headers.site, &other.headers.site),
+            ("Date", &self.headers.date, &other.headers.date),
+            ("Round", &self.headers.round, &other.headers.round),
+            ("White", &self.headers.white, &other.headers.white),
+            ("Black", &self.headers.black, &other.headers.black),
+            ("Result", &self.headers.result, &other.headers.result),
+        ] {
+            if expected != actual {
+                differences.push(Difference::Header {
+                    tag,
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                });
+            }
+        }
+
+        // find the first ply at which the moves differ, including one game ending early
+        let san = |moves: &[super::SanPlusWrapper], i: usize| moves.get(i).map(|m| m.0.to_string());
+        let plies = self.moves.len().max(other.moves.len());
+        if let Some(i) = (0..plies).find(|i| san(&self.moves, *i) != san(&other.moves, *i)) {
+            differences.push(Difference::Move {
+                ply: i + 1,
+                expected: san(&self.moves, i),
+                actual: san(&other.moves, i),
+            });
+        }
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    /// Tests that equal games have no differences.
+    fn diff_equal_games() {
+        let game = PgnData::from_str("[White \"A\"]\n\n1. e4 e5 *").unwrap();
+        assert!(game.diff(&game.clone()).is_empty());
+    }
+
+    #[test]
+    /// Tests that mismatched headers and the first differing ply are reported.
+    fn diff_headers_and_first_ply() {
+        let expected = PgnData::from_str("[White \"A\"]\n\n1. e4 e5 2. Nf3 Nc6 *").unwrap();
+        let actual = PgnData::from_str("[White \"B\"]\n\n1. e4 e5 2. Nc3 Nf6 *").unwrap();
+        let differences = expected.diff(&actual);
+        assert_eq!(differences.len(), 2);
+        assert_eq!(
+            differences[0].to_string(),
+            "White: expected \"A\", got \"B\""
+        );
+        assert_eq!(differences[1].to_string(), "ply 3: expected Nf3, got Nc3");
+    }
+
+    #[test]
+    /// Tests that a game ending early is reported at the first missing ply.
+    fn diff_game_ends_early() {
+        let expected = PgnData::from_str("1. e4 e5 2. Nf3 *").unwrap();
+        let actual = PgnData::from_str("1. e4 e5 *").unwrap();
+        assert_eq!(
+            expected.diff(&actual),
+            [Difference::Move {
+                ply: 3,
+                expected: Some(String::from("Nf3")),
+                actual: None,
+            }]
+        );
+        assert_eq!(
+            actual.diff(&expected)[0].to_string(),
+            "ply 3: expected end of game, got Nf3"
+        );
+    }
+}
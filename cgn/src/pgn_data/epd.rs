@@ -0,0 +1,97 @@
+use super::PgnData;
+use anyhow::Result;
+use shakmaty::{fen::Epd, Chess, EnPassantMode};
+
+/// An EPD operation to append to each exported position.
+#[derive(Clone, Debug)]
+pub enum EpdOp {
+    /// `id` operation holding the ply number of the position, e.g. `id "12";`.
+    PlyId,
+    /// `c0` comment operation holding the given text, e.g. `c0 "Carlsen vs Ter-Sahakyan";`.
+    Comment(String),
+    /// Any other operation given as an opcode and a raw operand, e.g. `("acd", "20")`.
+    Custom(String, String),
+}
+
+impl EpdOp {
+    /// Formats the operation for the position at the given ply.
+    fn format(&self, ply: usize) -> String {
+        match self {
+            EpdOp::PlyId => format!("id \"{}\";", ply),
+            EpdOp::Comment(text) => format!("c0 \"{}\";", text),
+            EpdOp::Custom(opcode, operand) => format!("{} {};", opcode, operand),
+        }
+    }
+}
+
+impl PgnData {
+    /// Exports every position of the game as an EPD string, starting with the initial
+    /// position and ending with the final position. Each EPD is followed by the given operations.
+    pub fn to_epd_positions(&self, ops: &[EpdOp]) -> Result<Vec<String>> {
+        Ok(self
+            .replay()?
+            .iter()
+            .enumerate()
+            .map(|(ply, pos)| epd_with_ops(pos, ops, ply))
+            .collect())
+    }
+}
+
+/// Formats a position as an EPD string followed by the given operations.
+fn epd_with_ops(pos: &Chess, ops: &[EpdOp], ply: usize) -> String {
+    let mut epd = Epd::from_position(pos.clone(), EnPassantMode::Legal).to_string();
+    for op in ops {
+        epd.push(' ');
+        epd.push_str(&op.format(ply));
+    }
+    epd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Example PGN string.
+    const PGN_STR_EXAMPLE: &str = r#"[Event "Example"]
+[Site ""]
+[Date "2023.01.03"]
+[Round "?"]
+[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "*"]
+
+1. e4 e5 2. Nf3 *"#;
+
+    #[test]
+    /// Tests that an EPD is produced for the initial position and after every move.
+    fn epd_per_position() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let epds = pgn_data.to_epd_positions(&[]).unwrap();
+        assert_eq!(epds.len(), 4);
+        assert_eq!(
+            epds[0],
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -"
+        );
+        assert_eq!(
+            epds[3],
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq -"
+        );
+    }
+
+    #[test]
+    /// Tests that the operations are appended to each EPD in order.
+    fn epd_with_operations() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let ops = [
+            EpdOp::PlyId,
+            EpdOp::Comment("Carlsen".to_string()),
+            EpdOp::Custom("acd".to_string(), "20".to_string()),
+        ];
+        let epds = pgn_data.to_epd_positions(&ops).unwrap();
+        assert_eq!(
+            epds[1],
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"1\"; c0 \"Carlsen\"; acd 20;"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use super::{PgnData, PgnHeaders, SanPlusWrapper};
+use anyhow::{anyhow, Result};
+use shakmaty::{san::SanPlus, Chess, Move, Position};
+
+/// Builds a game by playing moves on a position, recording the SAN of each move.
+/// This is the authoring counterpart to `PgnData::replay`.
+/// Games always start from the standard starting position.
+pub struct GameBuilder {
+    pos: Chess,                 // The position after the moves played so far
+    moves: Vec<SanPlusWrapper>, // The moves played so far
+}
+
+impl GameBuilder {
+    /// Creates a new GameBuilder at the standard starting position.
+    pub fn new() -> GameBuilder {
+        GameBuilder {
+            pos: Chess::default(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Plays a move, recording it with its check or checkmate suffix.
+    /// Returns an error if the move is not legal in the current position.
+    pub fn play(&mut self, m: &Move) -> Result<()> {
+        if !self.pos.is_legal(m) {
+            return Err(anyhow!(
+                "Illegal move {} at ply {}",
+                m,
+                self.moves.len() + 1
+            ));
+        }
+        let san_plus = SanPlus::from_move_and_play_unchecked(&mut self.pos, m);
+        self.moves.push(SanPlusWrapper(san_plus));
+        Ok(())
+    }
+
+    /// Returns the position after the moves played so far.
+    pub fn position(&self) -> &Chess {
+        &self.pos
+    }
+
+    /// Finishes the game with the given headers.
+    pub fn finish(self, headers: PgnHeaders) -> PgnData {
+        PgnData {
+            headers,
+            moves: self.moves,
+        }
+    }
+}
+
+impl Default for GameBuilder {
+    /// Creates a new GameBuilder.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::uci::Uci;
+
+    /// Parses a UCI move in the current position of the builder.
+    fn uci_move(builder: &GameBuilder, uci: &str) -> Move {
+        uci.parse::<Uci>()
+            .unwrap()
+            .to_move(builder.position())
+            .unwrap()
+    }
+
+    #[test]
+    /// Tests that played moves are recorded with their check and checkmate suffixes.
+    fn game_builder_records_moves() {
+        let mut builder = GameBuilder::new();
+        for uci in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            let m = uci_move(&builder, uci);
+            builder.play(&m).unwrap();
+        }
+        assert!(builder.position().is_checkmate());
+
+        let mut headers = PgnHeaders::new();
+        headers.result = String::from("0-1");
+        let game = builder.finish(headers);
+        let moves: Vec<String> = game.moves.iter().map(|m| m.0.to_string()).collect();
+        assert_eq!(moves, ["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(game.headers.result, "0-1");
+        assert_eq!(game.replay().unwrap().len(), 5);
+    }
+
+    #[test]
+    /// Tests that an illegal move is rejected and not recorded.
+    fn game_builder_illegal_move() {
+        let mut builder = GameBuilder::new();
+        let m = uci_move(&builder, "e2e4");
+        builder.play(&m).unwrap();
+        assert!(builder.play(&m).is_err());
+        assert_eq!(builder.finish(PgnHeaders::new()).moves.len(), 1);
+    }
+}
@@ -1,7 +1,20 @@
+mod content_id;
+mod diff;
+mod epd;
+mod game_builder;
+mod pgn_db_iter;
 mod pgn_vistor;
+mod reduced_format;
 mod san_plus_wrapper;
 
+pub use diff::Difference;
+pub use epd::EpdOp;
+pub use game_builder::GameBuilder;
+pub use pgn_db_iter::{pgn_db_into_iter, GameDelimiter, PgnDBIter, PgnGameIterExt};
+pub use pgn_vistor::{DEFAULT_MAX_HEADER_BYTES, DEFAULT_MAX_MOVES};
+pub use reduced_format::ReducedFormatReport;
 pub use san_plus_wrapper::SanPlusWrapper;
+use shakmaty::{Chess, Position};
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 /// PGN headers struct that holds the headers of a PGN game.
@@ -30,6 +43,41 @@ impl PgnHeaders {
         }
     }
 
+    /// Sets the header for the given PGN tag.
+    /// Tags outside of the seven tag roster are ignored.
+    pub(crate) fn set_tag(&mut self, key: &str, value: String) {
+        match key {
+            "Event" => self.event = value,
+            "Site" => self.site = value,
+            "Date" => self.date = value,
+            "Round" => self.round = value,
+            "White" => self.white = value,
+            "Black" => self.black = value,
+            "Result" => self.result = value,
+            _ => (),
+        }
+    }
+
+    /// Replaces empty tags with the placeholders of the PGN specification: `????.??.??` for
+    /// the Date, `*` for the Result and `?` for the others. `to_string` writes empty tags as-is,
+    /// which some strict parsers reject; `to_string_with_placeholders` writes the placeholders
+    /// without changing the headers.
+    pub fn fill_empty_tags(&mut self) {
+        for (tag, value) in [
+            ("Event", &mut self.event),
+            ("Site", &mut self.site),
+            ("Date", &mut self.date),
+            ("Round", &mut self.round),
+            ("White", &mut self.white),
+            ("Black", &mut self.black),
+            ("Result", &mut self.result),
+        ] {
+            if value.is_empty() {
+                *value = String::from(tag_placeholder(tag));
+            }
+        }
+    }
+
     // Checks if the PgnHeaders struct is empty
     pub fn is_empty(&self) -> bool {
         self.event.is_empty()
@@ -55,7 +103,7 @@ impl Default for PgnHeaders {
 /// A PGN game is in 'reduced export format' if abide by the following rules:
 /// 1) There are no comments.
 /// 2) Only the 7 mandatory tags are used (Event, Site, Date, Round, White, Black, Result).
-/// 3) There are no recursive annotations.
+/// 3) There are no recursive annotations (variations are dropped when parsing).
 /// 4) There are no numeric annotation glyphs.
 pub struct PgnData {
     pub headers: PgnHeaders,
@@ -71,10 +119,223 @@ impl PgnData {
         }
     }
 
+    /// Creates a PgnData struct with empty headers from the given moves.
+    /// The moves are not checked for legality.
+    pub fn from_moves(moves: impl IntoIterator<Item = SanPlusWrapper>) -> PgnData {
+        PgnData {
+            headers: PgnHeaders::new(),
+            moves: moves.into_iter().collect(),
+        }
+    }
+
     /// Clear headers from the PgnData struct.
     pub fn clear_headers(&mut self) {
         self.headers = PgnHeaders::new();
     }
+
+    /// Returns a copy of the PgnData struct with empty headers, leaving the original intact.
+    pub fn without_headers(&self) -> PgnData {
+        PgnData::from_moves(self.moves.iter().cloned())
+    }
+
+    /// Clears every header except the given PGN tags (e.g. `["Event", "Date", "Result"]`).
+    /// Tag names are case sensitive, and tags outside of the seven tag roster are ignored.
+    pub fn retain_headers(&mut self, tags: &[&str]) {
+        let headers = std::mem::take(&mut self.headers);
+        for (tag, value) in [
+            ("Event", headers.event),
+            ("Site", headers.site),
+            ("Date", headers.date),
+            ("Round", headers.round),
+            ("White", headers.white),
+            ("Black", headers.black),
+            ("Result", headers.result),
+        ] {
+            if tags.contains(&tag) {
+                self.headers.set_tag(tag, value);
+            }
+        }
+    }
+
+    /// Replays the moves from the starting position, returning the position before each move
+    /// followed by the final position.
+    /// The error for the first illegal move gives its ply and the FEN of its position.
+    pub fn replay(&self) -> anyhow::Result<Vec<Chess>> {
+        let mut pos = Chess::default();
+        let mut positions = Vec::with_capacity(self.moves.len() + 1);
+        for (i, san_plus) in self.moves.iter().enumerate() {
+            let m = san_plus.0.san.to_move(&pos).map_err(|_| {
+                let fen =
+                    shakmaty::fen::Fen::from_position(pos.clone(), shakmaty::EnPassantMode::Legal);
+                anyhow::anyhow!(
+                    "Illegal move {} at ply {} in position {}",
+                    san_plus.0,
+                    i + 1,
+                    fen
+                )
+            })?;
+            positions.push(pos.clone());
+            pos.play_unchecked(&m);
+        }
+        positions.push(pos);
+        Ok(positions)
+    }
+
+    /// Checks that every move is legal, returning an error with the ply and the FEN of the
+    /// position of the first illegal move.
+    pub fn check_moves(&self) -> anyhow::Result<()> {
+        self.replay().map(|_| ())
+    }
+
+    /// Returns the side to move at the given ply, where ply 0 is the starting position
+    /// and ply `moves.len()` is the final position.
+    /// Games always start from the standard starting position, so White moves first.
+    pub fn side_to_move_at(&self, ply: usize) -> anyhow::Result<shakmaty::Color> {
+        self.check_ply(ply)?;
+        let start = Chess::default().turn();
+        Ok(if ply.is_multiple_of(2) { start } else { !start })
+    }
+
+    /// Returns the material balance at the given ply from White's point of view (positive if
+    /// White is ahead), where ply 0 is the starting position and ply `moves.len()` is the final
+    /// position. Pawns are worth 1, knights and bishops 3, rooks 5, queens 9 and kings 0.
+    pub fn material_balance_at(&self, ply: usize) -> anyhow::Result<i32> {
+        self.check_ply(ply)?;
+        let pos = &self.replay()?[ply];
+
+        // sum the value of each side's pieces
+        let value = |material: shakmaty::ByRole<u8>| {
+            i32::from(material.pawn)
+                + 3 * i32::from(material.knight)
+                + 3 * i32::from(material.bishop)
+                + 5 * i32::from(material.rook)
+                + 9 * i32::from(material.queen)
+        };
+        let material = pos.board().material();
+        Ok(value(material.white) - value(material.black))
+    }
+
+    /// Checks that the ply is within the game, where ply `moves.len()` is the final position.
+    fn check_ply(&self, ply: usize) -> anyhow::Result<()> {
+        if ply > self.moves.len() {
+            return Err(anyhow::anyhow!(
+                "Ply {} is beyond the end of the game ({} plies)",
+                ply,
+                self.moves.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sets the result from the final position of the game, if it ended on the board and
+    /// its result is empty or unknown ("*").
+    /// Checkmate is a win for the side that delivered it, and stalemate, insufficient material,
+    /// the fifty-move rule and threefold repetition are draws.
+    /// Any other result is kept, since a game can also end by resignation, time forfeit or
+    /// agreement without its final position showing it.
+    pub fn set_result_from_moves(&mut self) -> anyhow::Result<()> {
+        let result = result_from_positions(&self.replay()?)?;
+        if result != "*" && matches!(self.headers.result.as_str(), "" | "*") {
+            self.headers.result = result.to_string();
+        }
+        Ok(())
+    }
+
+    /// Appends a move given in SAN, checking that it is legal in the final position of the game.
+    /// If the move delivers checkmate, the result is set to a win for the side that played it.
+    pub fn append_move_san(&mut self, san: &str) -> anyhow::Result<()> {
+        let san_plus = shakmaty::san::SanPlus::from_ascii(san.as_bytes())?;
+        let positions = self.replay()?;
+        let mut pos = positions
+            .last()
+            .ok_or(anyhow::anyhow!("No final position"))?
+            .clone();
+        let m = san_plus.san.to_move(&pos)?;
+
+        // store the move with its check and checkmate suffix as it would be parsed
+        let san_plus = shakmaty::san::SanPlus::from_move_and_play_unchecked(&mut pos, &m);
+        self.moves.push(SanPlusWrapper(san_plus));
+        if pos.is_checkmate() {
+            self.headers.result = match pos.turn() {
+                shakmaty::Color::White => "0-1",
+                shakmaty::Color::Black => "1-0",
+            }
+            .to_string();
+        }
+        Ok(())
+    }
+
+    /// Creates a PgnData struct from space-separated UCI moves (e.g. `e2e4 e7e5 e1g1 e7e8q`),
+    /// converting each move to SAN. The result is set from the final position,
+    /// or `*` if the game didn't end on the board.
+    /// Only the standard starting position is supported, so `start` must be `None`
+    /// or the FEN of the standard starting position.
+    pub fn from_uci(start: Option<&str>, moves: &str) -> anyhow::Result<PgnData> {
+        if let Some(fen) = start {
+            let pos: Chess = fen
+                .parse::<shakmaty::fen::Fen>()?
+                .into_position(shakmaty::CastlingMode::Standard)?;
+            if pos != Chess::default() {
+                return Err(anyhow::anyhow!(
+                    "Only the standard starting position is supported"
+                ));
+            }
+        }
+
+        let mut pgn_data = PgnData::new();
+        pgn_data.headers.result = String::from("*");
+        let mut pos = Chess::default();
+        for uci in moves.split_whitespace() {
+            let m = uci.parse::<shakmaty::uci::Uci>()?.to_move(&pos)?;
+            let san_plus = shakmaty::san::SanPlus::from_move_and_play_unchecked(&mut pos, &m);
+            pgn_data.moves.push(SanPlusWrapper(san_plus));
+        }
+        pgn_data.set_result_from_moves()?;
+        Ok(pgn_data)
+    }
+
+    /// Returns the size in bytes of the zlib compressed header block stored by the huffman strategy.
+    /// Empty headers aren't stored, so their size is 0.
+    pub fn headers_byte_size(&self) -> anyhow::Result<usize> {
+        let headers = crate::compression::huffman::encoder::compress_headers(&self.headers)?;
+        Ok(headers.len() / 8)
+    }
+
+    /// Returns a copy of only the headers of the PgnData struct.
+    pub fn headers_only(&self) -> PgnHeaders {
+        self.headers.clone()
+    }
+
+    /// Returns an iterator over the (tag, value) pairs of the headers, in the PGN order of the
+    /// seven tag roster. Empty headers are included, as they are written when formatting.
+    pub fn headers_iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        [
+            ("Event", self.headers.event.as_str()),
+            ("Site", self.headers.site.as_str()),
+            ("Date", self.headers.date.as_str()),
+            ("Round", self.headers.round.as_str()),
+            ("White", self.headers.white.as_str()),
+            ("Black", self.headers.black.as_str()),
+            ("Result", self.headers.result.as_str()),
+        ]
+        .into_iter()
+    }
+
+    /// Returns a copy of only the moves of the PgnData struct.
+    pub fn moves_only(&self) -> Vec<SanPlusWrapper> {
+        self.moves.clone()
+    }
+
+    /// Returns a new game of the first `n` plies (or every ply, if the game is shorter),
+    /// with the same headers but an unfinished `*` result. The original game is left intact.
+    pub fn opening_moves(&self, n: usize) -> PgnData {
+        let mut headers = self.headers.clone();
+        headers.result = String::from("*");
+        PgnData {
+            headers,
+            moves: self.moves.iter().take(n).cloned().collect(),
+        }
+    }
 }
 
 impl Default for PgnData {
@@ -84,49 +345,285 @@ impl Default for PgnData {
     }
 }
 
+impl PgnData {
+    /// Parses a PGN string into its mainline PgnData struct and a PgnData struct for each
+    /// variation. Each variation holds the moves leading up to it followed by its own moves.
+    /// An empty list of variations means nothing was dropped when parsing the mainline.
+    pub fn from_str_with_variations(s: &str) -> Result<(PgnData, Vec<PgnData>), std::io::Error> {
+        let mut visitor = pgn_vistor::PgnVisitor::new_with_variations();
+        let mainline = read_game(s, &mut visitor)?;
+        Ok((mainline, visitor.take_variations()))
+    }
+
+    /// Parses a PGN string into a PgnData struct, also returning the numeric annotation glyphs
+    /// (NAGs, e.g. `$1` or `!`) of each mainline move, in the order they were written.
+    /// Most moves have none, and the NAGs of variations are dropped with them.
+    pub fn from_str_with_nags(s: &str) -> Result<(PgnData, Vec<Vec<u8>>), std::io::Error> {
+        let mut visitor = pgn_vistor::PgnVisitor::new();
+        let pgn_data = read_game(s, &mut visitor)?;
+        Ok((pgn_data, visitor.take_nags()))
+    }
+
+    /// Parses a PGN string into a PgnData struct, also returning the tags that appear more
+    /// than once. As with `from_str`, the first value of a duplicated tag is kept.
+    pub fn from_str_with_duplicate_tags(s: &str) -> Result<(PgnData, Vec<String>), std::io::Error> {
+        let mut visitor = pgn_vistor::PgnVisitor::new();
+        let pgn_data = read_game(s, &mut visitor)?;
+        Ok((pgn_data, visitor.take_duplicate_tags()))
+    }
+
+    /// Parses a PGN string into a PgnData struct, returning an error if the game has more
+    /// than `max_moves` moves or more than `max_header_bytes` bytes of tags.
+    /// `from_str` uses the limits `DEFAULT_MAX_MOVES` and `DEFAULT_MAX_HEADER_BYTES`.
+    pub fn from_str_with_limits(
+        s: &str,
+        max_moves: usize,
+        max_header_bytes: usize,
+    ) -> Result<PgnData, std::io::Error> {
+        let mut visitor = pgn_vistor::PgnVisitor::with_limits(max_moves, max_header_bytes);
+        read_game(s, &mut visitor)
+    }
+}
+
+/// Reads a single game from a PGN string with the given visitor,
+/// returning an error if the game exceeds the limits of the visitor.
+fn read_game(s: &str, visitor: &mut pgn_vistor::PgnVisitor) -> Result<PgnData, std::io::Error> {
+    let s = normalize_pgn_str(s);
+    let pgn_data = pgn_reader::BufferedReader::new_cursor(s.as_bytes())
+        .read_game(visitor)?
+        .ok_or(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Failed to read PGN game from string",
+        ))?;
+    match visitor.limit_exceeded() {
+        Some(limit) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, limit)),
+        None => Ok(pgn_data),
+    }
+}
+
 impl std::str::FromStr for PgnData {
     type Err = std::io::Error;
 
     /// Parses a PGN string into a PgnData struct.
+    /// Only the mainline is kept, any variations are skipped.
+    /// If a tag appears more than once, the first value is kept.
+    /// Games over the default move and header size limits are rejected, see `from_str_with_limits`.
+    /// A leading UTF-8 BOM and blank lines are stripped, and CRLF line endings are normalized
+    /// to LF, so they parse identically.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut visitor = pgn_vistor::PgnVisitor::new();
-        pgn_reader::BufferedReader::new_cursor(&s)
-            .read_game(&mut visitor)?
-            .ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Failed to read PGN game from string",
-            ))
+        read_game(s, &mut visitor)
     }
 }
 
-impl std::fmt::Display for PgnData {
-    /// Formats the PgnData struct into a PGN string.
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // Create a string buffer and write the headers to it
+impl FromIterator<SanPlusWrapper> for PgnData {
+    /// Collects moves into a PgnData struct with empty headers.
+    fn from_iter<I: IntoIterator<Item = SanPlusWrapper>>(iter: I) -> Self {
+        PgnData::from_moves(iter)
+    }
+}
+
+impl TryFrom<&str> for PgnData {
+    type Error = std::io::Error;
+
+    /// Parses a PGN string into a PgnData struct.
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for PgnData {
+    type Error = std::io::Error;
+
+    /// Parses a PGN string into a PgnData struct.
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Strips a leading UTF-8 BOM and blank lines, and normalizes CRLF line endings to LF.
+fn normalize_pgn_str(s: &str) -> std::borrow::Cow<'_, str> {
+    let s = s.trim_start_matches('\u{feff}').trim_start();
+    if s.contains("\r\n") {
+        std::borrow::Cow::Owned(s.replace("\r\n", "\n"))
+    } else {
+        std::borrow::Cow::Borrowed(s)
+    }
+}
+
+/// Gets the result of a game from its positions, as returned by `PgnData::replay`.
+/// Draws that can only be claimed, by the fifty-move rule or threefold repetition, are
+/// treated as claimed.
+fn result_from_positions(positions: &[Chess]) -> anyhow::Result<&'static str> {
+    let pos = positions
+        .last()
+        .ok_or(anyhow::anyhow!("No final position"))?;
+    if pos.is_checkmate() {
+        return Ok(match pos.turn() {
+            shakmaty::Color::White => "0-1",
+            shakmaty::Color::Black => "1-0",
+        });
+    }
+
+    // positions repeat if they have the same board, side to move, castling and en passant rights
+    let epd = |pos: &Chess| {
+        shakmaty::fen::Epd::from_position(pos.clone(), shakmaty::EnPassantMode::Legal).to_string()
+    };
+    let final_epd = epd(pos);
+    let repetitions = positions.iter().filter(|p| epd(p) == final_epd).count();
+
+    if pos.is_stalemate()
+        || pos.is_insufficient_material()
+        || pos.halfmoves() >= 100
+        || repetitions >= 3
+    {
+        Ok("1/2-1/2")
+    } else {
+        Ok("*")
+    }
+}
+
+/// Escapes quotes and backslashes in a tag value, as required by the PGN specification.
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl PgnData {
+    /// Writes the game as PGN to a writer, always using LF line endings.
+    /// The output is the same as `to_string`, but the tag lines and the wrapped lines of the
+    /// movetext are written to the writer as they are formatted, rather than as a whole String.
+    pub fn write_pgn<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        write!(w, "{}", self)
+    }
+
+    /// Writes the game as PGN to a writer like `write_pgn`, writing empty tags as the
+    /// placeholders of the PGN specification, as `to_string_with_placeholders` does.
+    pub fn write_pgn_with_placeholders<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        write!(w, "{}", WithPlaceholders(self))
+    }
+
+    /// Formats the game as PGN like `to_string`, writing empty tags as the placeholders of the
+    /// PGN specification (see `PgnHeaders::fill_empty_tags`), so the output is valid PGN even
+    /// when the source omitted values. An empty result is also written as `*` after the moves.
+    pub fn to_string_with_placeholders(&self) -> String {
+        WithPlaceholders(self).to_string()
+    }
+
+    /// Returns the game as PGN bytes, the same as `to_string().into_bytes()`.
+    pub fn to_pgn_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_pgn(&mut bytes)
+            .expect("Writing to a Vec never fails");
+        bytes
+    }
+
+    /// Returns the CRC32 of the canonical PGN text of the game, as given by `to_string`.
+    /// The canonical text has all seven tags, LF line endings and movetext wrapped to 80
+    /// characters, so games that differ only in how their source PGN was written have the
+    /// same checksum. It can be stored alongside compressed data, and recomputed after
+    /// decompression to detect corruption.
+    pub fn checksum_pgn(&self) -> u32 {
+        let mut crc = flate2::Crc::new();
+        crc.update(&self.to_pgn_bytes());
+        crc.sum()
+    }
+
+    /// Formats the game as PGN like `to_string`, writing the NAGs of each move as `$N` after it.
+    /// `nags` holds the NAGs of each move, as returned by `from_str_with_nags`.
+    pub fn to_string_with_nags(&self, nags: &[Vec<u8>]) -> String {
+        let mut s = String::new();
+        self.fmt_pgn(&mut s, nags, false)
+            .expect("Writing to a String never fails");
+        s
+    }
+
+    /// Writes the game as PGN with the given NAGs of each move after it, wrapping every line
+    /// to 80 characters. If `placeholders` is set, empty tags are written as their placeholders.
+    fn fmt_pgn(
+        &self,
+        f: &mut impl std::fmt::Write,
+        nags: &[Vec<u8>],
+        placeholders: bool,
+    ) -> std::fmt::Result {
+        // Write the headers, wrapping only the tag lines that don't fit
+        for (tag, value) in self.headers_iter() {
+            let value = escape_tag_value(value_or_placeholder(tag, value, placeholders));
+            if tag.len() + value.len() + 5 <= 80 && !value.contains('\n') {
+                writeln!(f, "[{} \"{}\"]", tag, value)?;
+            } else {
+                write_wrapped(f, &format!("[{} \"{}\"]", tag, value))?;
+                f.write_char('\n')?;
+            }
+        }
+        f.write_char('\n')?;
+        let result = value_or_placeholder("Result", &self.headers.result, placeholders);
+        write_wrapped(f, &self.movetext(nags, result))
+    }
+
+    /// Formats the moves and the given result of the game as PGN before it is wrapped,
+    /// writing the given NAGs of each move after it.
+    fn movetext(&self, nags: &[Vec<u8>], result: &str) -> String {
         let mut s = String::new();
-        s.push_str(&format!("[Event \"{}\"]\n", self.headers.event));
-        s.push_str(&format!("[Site \"{}\"]\n", self.headers.site));
-        s.push_str(&format!("[Date \"{}\"]\n", self.headers.date));
-        s.push_str(&format!("[Round \"{}\"]\n", self.headers.round));
-        s.push_str(&format!("[White \"{}\"]\n", self.headers.white));
-        s.push_str(&format!("[Black \"{}\"]\n", self.headers.black));
-        s.push_str(&format!("[Result \"{}\"]\n", self.headers.result));
-
-        // Write the moves to the string buffer
-        s.push('\n');
         for (i, san_plus) in self.moves.iter().enumerate() {
             if i % 2 == 0 {
                 s.push_str(&format!("{}. ", i / 2 + 1));
             }
             s.push_str(&san_plus.0.to_string());
             s.push(' ');
+            for nag in nags.get(i).into_iter().flatten() {
+                s.push_str(&format!("${} ", nag));
+            }
         }
 
-        // Write the result to the string buffer
-        s.push_str(self.headers.result.as_str());
+        // Write the result after the moves
+        s.push_str(result);
+        s
+    }
+}
+
+/// Returns the placeholder the PGN specification gives for an unknown value of the tag.
+fn tag_placeholder(tag: &str) -> &'static str {
+    match tag {
+        "Date" => "????.??.??",
+        "Result" => "*",
+        _ => "?",
+    }
+}
 
-        //  Wrap the string buffer to 80 characters and write it to the formatter
-        write!(f, "{}", textwrap::fill(&s, 80))
+/// Returns the value of the tag, or its placeholder if it is empty and `placeholders` is set.
+fn value_or_placeholder<'a>(tag: &str, value: &'a str, placeholders: bool) -> &'a str {
+    if placeholders && value.is_empty() {
+        tag_placeholder(tag)
+    } else {
+        value
+    }
+}
+
+/// Formats a game as PGN, writing empty tags as their placeholders.
+struct WithPlaceholders<'a>(&'a PgnData);
+
+impl std::fmt::Display for WithPlaceholders<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt_pgn(f, &[], true)
+    }
+}
+
+/// Writes the text wrapped to 80 characters, the same as `textwrap::fill`
+/// but without building the wrapped text as a String.
+fn write_wrapped(f: &mut impl std::fmt::Write, text: &str) -> std::fmt::Result {
+    for (i, line) in textwrap::wrap(text, 80).iter().enumerate() {
+        if i > 0 {
+            f.write_char('\n')?;
+        }
+        f.write_str(line)?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for PgnData {
+    /// Formats the PgnData struct into a PGN string, always using LF line endings.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_pgn(f, &[], false)
     }
 }
 
@@ -172,6 +669,17 @@ Qd2 Bb4 29. c3 Be7 30. Nf2 dxc3 31. bxc3 Nd8 32. Bb1 Ne6 33. Nh3 Bc5 34. Ba2 Rd8
 Bxf7+ Kf8 42. Qxf2 Rxd1 43. Bxg6 Qd6 44. g5 Qd3 45. Qc5+ Qd6 46. Qc8+ Kg7 47.
 Qxb7+ Kf8 48. Qf7# 1-0"#;
 
+    /// Example PGN string with nested variations.
+    const PGN_STR_EXAMPLE_VARIATIONS: &str = r#"[Event "Variations"]
+[Site ""]
+[Date "2023.01.03"]
+[Round "?"]
+[White "White"]
+[Black "Black"]
+[Result "1-0"]
+
+1. e4 e5 (1... c5 2. Nf3 (2. Nc3)) 2. Nf3 Nc6 3. Bb5 (3. Bc4) 1-0"#;
+
     #[test]
     /// Tests if the PgnData struct can be parsed and then converted back to a string.
     fn parsed_eq_original() {
@@ -180,6 +688,30 @@ Qxb7+ Kf8 48. Qf7# 1-0"#;
         assert_eq!(pgn_str, pgn_data.to_string());
     }
 
+    #[test]
+    /// Tests if a PgnData struct can be converted from a string slice and an owned string.
+    fn try_from_str_and_string() {
+        let from_str = PgnData::try_from(PGN_STR_EXAMPLE).unwrap();
+        let from_string = PgnData::try_from(PGN_STR_EXAMPLE.to_string()).unwrap();
+        assert_eq!(from_str.to_string(), PGN_STR_EXAMPLE);
+        assert_eq!(from_string.to_string(), PGN_STR_EXAMPLE);
+    }
+
+    #[test]
+    /// Tests if header values with quotes and backslashes are escaped and round trip.
+    fn escapes_header_values() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.headers.event = r#"Foo "Bar" Open"#.to_string();
+        pgn_data.headers.site = r"C:\chess".to_string();
+        let pgn_str = pgn_data.to_string();
+        assert!(pgn_str.contains(r#"[Event "Foo \"Bar\" Open"]"#));
+        assert!(pgn_str.contains(r#"[Site "C:\\chess"]"#));
+
+        let parsed = PgnData::from_str(&pgn_str).unwrap();
+        assert_eq!(parsed.headers.event, pgn_data.headers.event);
+        assert_eq!(parsed.headers.site, pgn_data.headers.site);
+    }
+
     #[test]
     /// Tests if we can clear the headers from a PgnData struct.
     fn can_clear_headers() {
@@ -195,6 +727,476 @@ Qxb7+ Kf8 48. Qf7# 1-0"#;
         assert_eq!(pgn_data.headers.result, "");
     }
 
+    #[test]
+    /// Tests if only the listed headers are kept.
+    fn can_retain_headers() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.retain_headers(&["Event", "Date", "Result", "FOOBAR"]);
+        assert_eq!(
+            pgn_data.headers.event,
+            "Titled Tuesday Blitz January 03 Early 2023"
+        );
+        assert_eq!(pgn_data.headers.date, "2023.01.03");
+        assert_eq!(pgn_data.headers.result, "1-0");
+        assert_eq!(pgn_data.headers.round, "");
+        assert_eq!(pgn_data.headers.white, "");
+        assert_eq!(pgn_data.headers.black, "");
+
+        pgn_data.retain_headers(&[]);
+        assert!(pgn_data.headers.is_empty());
+    }
+
+    #[test]
+    /// Tests if replaying a game gives the position before each move and the final position.
+    fn can_replay() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let positions = pgn_data.replay().unwrap();
+        assert_eq!(positions.len(), pgn_data.moves.len() + 1);
+        assert_eq!(positions[0], Chess::default());
+        assert!(positions.last().unwrap().is_checkmate());
+    }
+
+    #[test]
+    /// Tests if replaying a game with an illegal move fails, giving the ply of the move.
+    fn replay_illegal_move() {
+        let pgn_data = PgnData::from_str("1. e4 e4 *").unwrap();
+        let err = pgn_data.replay().unwrap_err().to_string();
+        assert!(err.starts_with("Illegal move e4 at ply 2 in position"));
+        assert_eq!(pgn_data.check_moves().unwrap_err().to_string(), err);
+    }
+
+    #[test]
+    /// Tests if the side to move alternates from White, up to the final position.
+    fn can_get_side_to_move_at() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        assert_eq!(pgn_data.side_to_move_at(0).unwrap(), shakmaty::Color::White);
+        assert_eq!(pgn_data.side_to_move_at(1).unwrap(), shakmaty::Color::Black);
+        let positions = pgn_data.replay().unwrap();
+        assert_eq!(pgn_data.side_to_move_at(95).unwrap(), positions[95].turn());
+        assert!(pgn_data.side_to_move_at(96).is_err());
+    }
+
+    #[test]
+    /// Tests if the result is inferred from a checkmate by either side.
+    fn result_from_checkmate() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.headers.result = String::new();
+        pgn_data.set_result_from_moves().unwrap();
+        assert_eq!(pgn_data.headers.result, "1-0");
+
+        let mut pgn_data = PgnData::from_str("1. f3 e5 2. g4 Qh4# 0-1").unwrap();
+        pgn_data.set_result_from_moves().unwrap();
+        assert_eq!(pgn_data.headers.result, "0-1");
+    }
+
+    #[test]
+    /// Tests if the result is inferred from a stalemate.
+    fn result_from_stalemate() {
+        let pgn_str = "1. e3 a5 2. Qh5 Ra6 3. Qxa5 h5 4. h4 Rah6 5. Qxc7 f6 6. Qxd7+ Kf7 \
+            7. Qxb7 Qd3 8. Qxb8 Qh7 9. Qxc8 Kg6 10. Qe6 *";
+        let mut pgn_data = PgnData::from_str(pgn_str).unwrap();
+        pgn_data.set_result_from_moves().unwrap();
+        assert_eq!(pgn_data.headers.result, "1/2-1/2");
+    }
+
+    #[test]
+    /// Tests if the result is inferred as a draw by insufficient material or the fifty-move rule.
+    fn result_from_draw_rules() {
+        let position = |fen: &str| -> Chess {
+            shakmaty::fen::Fen::from_str(fen)
+                .unwrap()
+                .into_position(shakmaty::CastlingMode::Standard)
+                .unwrap()
+        };
+        let insufficient = position("8/8/4k3/8/8/3KB3/8/8 w - - 0 60");
+        assert_eq!(result_from_positions(&[insufficient]).unwrap(), "1/2-1/2");
+        let fifty_moves = position("8/8/4k3/8/8/3K4/3R4/8 b - - 100 90");
+        assert_eq!(result_from_positions(&[fifty_moves]).unwrap(), "1/2-1/2");
+        let forty_nine_moves = position("8/8/4k3/8/8/3K4/3R4/8 b - - 99 90");
+        assert_eq!(result_from_positions(&[forty_nine_moves]).unwrap(), "*");
+    }
+
+    #[test]
+    /// Tests if the result is inferred as a draw by threefold repetition.
+    fn result_from_threefold_repetition() {
+        let mut pgn_data = PgnData::from_str("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 *").unwrap();
+        pgn_data.headers.result = String::from("*");
+        pgn_data.set_result_from_moves().unwrap();
+        assert_eq!(pgn_data.headers.result, "*");
+        pgn_data.append_move_san("Ng1").unwrap();
+        pgn_data.append_move_san("Ng8").unwrap();
+        pgn_data.set_result_from_moves().unwrap();
+        assert_eq!(pgn_data.headers.result, "1/2-1/2");
+    }
+
+    #[test]
+    /// Tests if the result of a game that didn't end on the board is kept,
+    /// e.g. a resignation.
+    fn result_from_unfinished_game() {
+        for result in ["1-0", "0-1", "1/2-1/2", "*", ""] {
+            let mut pgn_data = PgnData::from_str("1. e4 e5 *").unwrap();
+            pgn_data.headers.result = String::from(result);
+            pgn_data.set_result_from_moves().unwrap();
+            assert_eq!(pgn_data.headers.result, result);
+        }
+    }
+
+    #[test]
+    /// Tests if a recorded result is kept even when the final position is terminal.
+    fn result_from_moves_keeps_recorded_result() {
+        let mut pgn_data = PgnData::from_str("1. f3 e5 2. g4 Qh4# *").unwrap();
+        pgn_data.headers.result = String::from("1/2-1/2");
+        pgn_data.set_result_from_moves().unwrap();
+        assert_eq!(pgn_data.headers.result, "1/2-1/2");
+    }
+
+    #[test]
+    /// Tests if legal moves can be appended, with checkmate setting the result.
+    fn can_append_move_san() {
+        let mut pgn_data = PgnData::new();
+        for san in ["f3", "e5", "g4"] {
+            pgn_data.append_move_san(san).unwrap();
+        }
+        assert_eq!(pgn_data.headers.result, "");
+        pgn_data.append_move_san("Qh4").unwrap();
+        assert_eq!(pgn_data.moves[3].0.to_string(), "Qh4#");
+        assert_eq!(pgn_data.headers.result, "0-1");
+    }
+
+    #[test]
+    /// Tests if illegal or malformed moves are rejected without changing the game.
+    fn append_illegal_move_san() {
+        let mut pgn_data = PgnData::new();
+        pgn_data.append_move_san("e4").unwrap();
+        assert!(pgn_data.append_move_san("e4").is_err());
+        assert!(pgn_data.append_move_san("not a move").is_err());
+        assert_eq!(pgn_data.moves.len(), 1);
+    }
+
+    #[test]
+    /// Tests if UCI moves, including castling and promotion, are converted to SAN.
+    fn can_parse_uci() {
+        let uci = "e2e4 d7d5 e4d5 c7c6 d5c6 g8f6 c6b7 e7e6 g1f3 f8e7 f1e2 e8g8 e1g1 a7a6 b7a8q";
+        let pgn_data = PgnData::from_uci(None, uci).unwrap();
+        let moves: Vec<String> = pgn_data.moves.iter().map(|m| m.0.to_string()).collect();
+        assert_eq!(moves[11], "O-O");
+        assert_eq!(moves[12], "O-O");
+        assert_eq!(moves[14], "bxa8=Q");
+        assert_eq!(pgn_data.headers.result, "*");
+
+        let pgn_data = PgnData::from_uci(
+            Some("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            "f2f3 e7e5 g2g4 d8h4",
+        )
+        .unwrap();
+        assert_eq!(
+            pgn_data.to_string().lines().last().unwrap(),
+            "1. f3 e5 2. g4 Qh4# 0-1"
+        );
+    }
+
+    #[test]
+    /// Tests if illegal UCI moves and custom starting positions are rejected.
+    fn from_uci_invalid() {
+        assert!(PgnData::from_uci(None, "e2e5").is_err());
+        assert!(PgnData::from_uci(None, "e2").is_err());
+        assert!(PgnData::from_uci(Some("8/8/8/8/8/8/8/K6k w - - 0 1"), "a1a2").is_err());
+    }
+
+    #[test]
+    /// Tests if the header block size matches the headers stored by the huffman strategy.
+    fn can_get_headers_byte_size() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        assert_eq!(pgn_data.headers_byte_size().unwrap(), 120);
+        pgn_data.clear_headers();
+        assert_eq!(pgn_data.headers_byte_size().unwrap(), 0);
+    }
+
+    #[test]
+    /// Tests if we can copy only the headers from a PgnData struct.
+    fn can_copy_headers_only() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let headers = pgn_data.headers_only();
+        assert_eq!(headers.event, "Titled Tuesday Blitz January 03 Early 2023");
+        assert_eq!(headers.white, "Magnus Carlsen");
+        assert_eq!(headers.result, "1-0");
+    }
+
+    #[test]
+    /// Tests if we can copy only the moves from a PgnData struct.
+    fn can_copy_moves_only() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let moves = pgn_data.moves_only();
+        assert_eq!(moves.len(), 95);
+        assert_eq!(moves[0].0.to_string(), "a4");
+        assert_eq!(moves[94].0.to_string(), "Qf7#");
+    }
+
+    #[test]
+    /// Tests if variations are dropped from the mainline when parsing a PGN string.
+    fn skips_variations() {
+        let pgn_str = PGN_STR_EXAMPLE_VARIATIONS;
+        let pgn_data = PgnData::from_str(pgn_str).unwrap();
+        let moves: Vec<String> = pgn_data.moves.iter().map(|m| m.0.to_string()).collect();
+        assert_eq!(moves, vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+    }
+
+    #[test]
+    /// Tests if variations can be extracted as separate games.
+    fn extracts_variations() {
+        let pgn_str = PGN_STR_EXAMPLE_VARIATIONS;
+        let (mainline, variations) = PgnData::from_str_with_variations(pgn_str).unwrap();
+        assert_eq!(mainline.moves.len(), 5);
+        assert_eq!(variations.len(), 3);
+
+        let lines: Vec<String> = variations
+            .iter()
+            .map(|v| {
+                v.moves
+                    .iter()
+                    .map(|m| m.0.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        assert!(lines.contains(&"e4 c5 Nf3".to_string()));
+        assert!(lines.contains(&"e4 c5 Nc3".to_string()));
+        assert!(lines.contains(&"e4 e5 Nf3 Nc6 Bc4".to_string()));
+        assert!(variations.iter().all(|v| v.headers.result == "*"));
+    }
+
+    #[test]
+    /// Tests if a game without variations extracts no variations.
+    fn extracts_no_variations() {
+        let (_, variations) = PgnData::from_str_with_variations(PGN_STR_EXAMPLE).unwrap();
+        assert!(variations.is_empty());
+    }
+
+    #[test]
+    /// Tests if a PGN string with CRLF line endings parses identically to one with LF.
+    fn parses_crlf_line_endings() {
+        let crlf = PGN_STR_EXAMPLE.replace('\n', "\r\n");
+        let pgn_data = PgnData::from_str(&crlf).unwrap();
+        assert_eq!(
+            pgn_data.headers.event,
+            "Titled Tuesday Blitz January 03 Early 2023"
+        );
+        assert_eq!(pgn_data.to_string(), PGN_STR_EXAMPLE);
+
+        let (mainline, _) = PgnData::from_str_with_variations(&crlf).unwrap();
+        assert_eq!(mainline.to_string(), PGN_STR_EXAMPLE);
+    }
+
+    #[test]
+    /// Tests if a PGN string with a UTF-8 BOM and leading blank lines parses identically.
+    fn parses_bom_and_leading_blank_lines() {
+        let pgn_str = format!("\u{feff}\n\n{}", PGN_STR_EXAMPLE);
+        let pgn_data = PgnData::from_str(&pgn_str).unwrap();
+        assert_eq!(
+            pgn_data.headers.event,
+            "Titled Tuesday Blitz January 03 Early 2023"
+        );
+        assert_eq!(pgn_data.to_string(), PGN_STR_EXAMPLE);
+    }
+
+    #[test]
+    /// Tests if writing the game as bytes matches the PGN string.
+    fn can_write_pgn_bytes() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        assert_eq!(pgn_data.to_pgn_bytes(), PGN_STR_EXAMPLE.as_bytes());
+
+        let mut writer = std::io::Cursor::new(Vec::new());
+        pgn_data.write_pgn(&mut writer).unwrap();
+        assert_eq!(writer.into_inner(), PGN_STR_EXAMPLE.as_bytes());
+    }
+
+    #[test]
+    /// Tests if the game is written the same as filling its unwrapped text to 80 characters,
+    /// including tag lines that are too long or contain a line break.
+    fn write_pgn_matches_fill() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.headers.event = "Titled Tuesday Blitz ".repeat(6);
+        pgn_data.headers.site = String::from("Line one\nline two");
+
+        let mut unwrapped = String::new();
+        for (tag, value) in pgn_data.headers_iter() {
+            unwrapped.push_str(&format!("[{} \"{}\"]\n", tag, escape_tag_value(value)));
+        }
+        unwrapped.push('\n');
+        unwrapped.push_str(&pgn_data.movetext(&[], &pgn_data.headers.result));
+        let expected = textwrap::fill(&unwrapped, 80);
+
+        assert_eq!(pgn_data.to_string(), expected);
+        assert_eq!(pgn_data.to_pgn_bytes(), expected.as_bytes());
+        assert_eq!(pgn_data.to_string_with_nags(&[]), expected);
+    }
+
+    #[test]
+    /// Tests if the first value of a duplicated tag is kept and the duplicate is reported.
+    fn keeps_first_duplicate_tag() {
+        let pgn_str = "[White \"Magnus Carlsen\"]\n[White \"Hikaru Nakamura\"]\n\n1. e4 *";
+        let pgn_data = PgnData::from_str(pgn_str).unwrap();
+        assert_eq!(pgn_data.headers.white, "Magnus Carlsen");
+
+        let (pgn_data, duplicates) = PgnData::from_str_with_duplicate_tags(pgn_str).unwrap();
+        assert_eq!(pgn_data.headers.white, "Magnus Carlsen");
+        assert_eq!(duplicates, vec!["White"]);
+
+        let (_, duplicates) = PgnData::from_str_with_duplicate_tags(PGN_STR_EXAMPLE).unwrap();
+        assert!(duplicates.is_empty());
+    }
+
+    #[test]
+    /// Tests if the material balance follows captures and rejects plies past the end.
+    fn material_balance_at() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        assert_eq!(pgn_data.material_balance_at(0).unwrap(), 0);
+        // 7... cxd4 wins a pawn for Black, then 8. Nxg6 hxg6 trades a knight for a bishop
+        assert_eq!(pgn_data.material_balance_at(14).unwrap(), -1);
+        assert_eq!(pgn_data.material_balance_at(15).unwrap(), 2);
+        assert_eq!(pgn_data.material_balance_at(16).unwrap(), -1);
+        assert!(pgn_data.material_balance_at(pgn_data.moves.len()).is_ok());
+        assert!(pgn_data
+            .material_balance_at(pgn_data.moves.len() + 1)
+            .is_err());
+    }
+
+    #[test]
+    /// Tests if empty tags are replaced with placeholders, and other tags are kept.
+    fn fill_empty_tags() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.headers.round = String::new();
+        pgn_data.headers.fill_empty_tags();
+        let pgn_str = pgn_data.to_string();
+        assert!(pgn_str.contains("[Site \"?\"]\n"));
+        assert!(pgn_str.contains("[Round \"?\"]\n"));
+        assert!(pgn_str.contains("[Date \"2023.01.03\"]\n"));
+        assert!(!pgn_str.contains("\"\""));
+
+        let mut headers = PgnHeaders::new();
+        headers.fill_empty_tags();
+        assert_eq!(headers.date, "????.??.??");
+        assert_eq!(headers.result, "*");
+    }
+
+    #[test]
+    /// Tests if empty tags are written as placeholders on output, without changing the game,
+    /// and the output parses back to the filled headers.
+    fn to_string_with_placeholders() {
+        let mut pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        pgn_data.headers.round = String::new();
+        let pgn_str = pgn_data.to_string_with_placeholders();
+        assert!(pgn_str.contains("[Site \"?\"]\n"));
+        assert!(pgn_str.contains("[Round \"?\"]\n"));
+        assert!(pgn_str.contains("[Date \"2023.01.03\"]\n"));
+        assert!(!pgn_str.contains("\"\""));
+        assert!(pgn_data.headers.round.is_empty());
+        assert!(pgn_data.to_string().contains("[Round \"\"]\n"));
+
+        let mut filled = pgn_data.clone();
+        filled.headers.fill_empty_tags();
+        assert_eq!(pgn_str, filled.to_string());
+        let mut bytes = Vec::new();
+        pgn_data.write_pgn_with_placeholders(&mut bytes).unwrap();
+        assert_eq!(bytes, pgn_str.as_bytes());
+
+        // a decompressed game without headers is written as valid PGN
+        let compressed =
+            crate::compression::huffman::compress_pgn_data(&pgn_data.without_headers()).unwrap();
+        let decompressed = crate::compression::huffman::decompress_pgn_data(&compressed).unwrap();
+        let pgn_str = decompressed.to_string_with_placeholders();
+        assert!(pgn_str.starts_with("[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n"));
+        assert!(pgn_str.ends_with("Qf7# *"));
+    }
+
+    #[test]
+    /// Tests if the checksum is over the canonical text, and changes with the game.
+    fn checksum_pgn() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let crlf = PgnData::from_str(&PGN_STR_EXAMPLE.replace('\n', "\r\n")).unwrap();
+        assert_eq!(pgn_data.checksum_pgn(), crlf.checksum_pgn());
+
+        let mut crc = flate2::Crc::new();
+        crc.update(PGN_STR_EXAMPLE.as_bytes());
+        assert_eq!(pgn_data.checksum_pgn(), crc.sum());
+
+        let mut changed = pgn_data.clone();
+        changed.moves.pop();
+        assert_ne!(pgn_data.checksum_pgn(), changed.checksum_pgn());
+        changed.clear_headers();
+        assert_ne!(pgn_data.checksum_pgn(), changed.checksum_pgn());
+    }
+
+    #[test]
+    /// Tests if the headers are iterated in PGN order, including empty ones.
+    fn headers_iter() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let headers: Vec<(&str, &str)> = pgn_data.headers_iter().collect();
+        assert_eq!(
+            headers,
+            [
+                ("Event", "Titled Tuesday Blitz January 03 Early 2023"),
+                ("Site", ""),
+                ("Date", "2023.01.03"),
+                ("Round", "?"),
+                ("White", "Magnus Carlsen"),
+                ("Black", "Samvel Ter-Sahakyan"),
+                ("Result", "1-0"),
+            ]
+        );
+    }
+
+    #[test]
+    /// Tests if the headers are dropped from a copy but kept in the original.
+    fn without_headers() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let stripped = pgn_data.without_headers();
+        assert!(stripped.headers.is_empty());
+        assert_eq!(stripped.moves.len(), pgn_data.moves.len());
+        assert_eq!(pgn_data.headers.white, "Magnus Carlsen");
+    }
+
+    #[test]
+    /// Tests if the opening moves are copied into a new unfinished game.
+    fn opening_moves() {
+        let pgn_data = PgnData::from_str(PGN_STR_EXAMPLE).unwrap();
+        let opening = pgn_data.opening_moves(4);
+        let sans: Vec<String> = opening.moves.iter().map(|m| m.0.to_string()).collect();
+        assert_eq!(sans, ["a4", "Nf6", "d4", "d5"]);
+        assert_eq!(opening.headers.white, "Magnus Carlsen");
+        assert_eq!(opening.headers.result, "*");
+        assert_eq!(pgn_data.headers.result, "1-0");
+        assert_eq!(pgn_data.moves.len(), 95);
+        assert_eq!(pgn_data.opening_moves(1000).moves.len(), 95);
+        assert!(pgn_data.opening_moves(0).moves.is_empty());
+    }
+
+    #[test]
+    /// Tests if a PgnData struct is built from moves with empty headers.
+    fn from_moves() {
+        let moves = ["e4", "e5", "Nf3"].map(|san| SanPlusWrapper::from_san_str(san).unwrap());
+        let pgn_data = PgnData::from_moves(moves.clone());
+        assert_eq!(pgn_data.moves.len(), 3);
+        assert!(pgn_data.headers.white.is_empty());
+        assert!(pgn_data.headers.result.is_empty());
+        assert_eq!(pgn_data.moves[2].0.to_string(), "Nf3");
+
+        let collected: PgnData = moves.into_iter().collect();
+        assert_eq!(collected.moves.len(), 3);
+    }
+
+    #[test]
+    /// Tests if games over the move or header size limits are rejected.
+    fn rejects_games_over_limits() {
+        assert!(PgnData::from_str_with_limits(PGN_STR_EXAMPLE, 95, 1000).is_ok());
+        assert!(PgnData::from_str_with_limits(PGN_STR_EXAMPLE, 94, 1000).is_err());
+        assert!(PgnData::from_str_with_limits(PGN_STR_EXAMPLE, 95, 100).is_err());
+
+        let pgn_str = "1. Nf3 Nf6 2. Ng1 Ng8 ".repeat(DEFAULT_MAX_MOVES / 4 + 1);
+        let err = PgnData::from_str(&pgn_str).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
     #[test]
     /// Tests if additional headers are ignored when parsing a PGN string.
     fn ignores_additional_headers() {
@@ -0,0 +1,297 @@
+use super::PgnData;
+use crate::archive::Strategy;
+use crate::compression::utils::to_padded_bytes;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    str::FromStr,
+};
+
+/// How the games of a PGN database are delimited.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum GameDelimiter {
+    /// A game ends at a tag line (starting with `[`) that follows a blank line,
+    /// so databases whose records don't start with `[Event` are also supported.
+    #[default]
+    NextTag,
+    /// A game ends at the first blank line after its movetext.
+    BlankLine,
+    /// A game ends at a line that is exactly the given separator, e.g. a line of dashes
+    /// or a form feed. The separator lines are not part of any game.
+    Custom(String),
+}
+
+/// An iterator over the games in a PGN database file.
+/// Games are delimited as given by a `GameDelimiter`, by default at the next tag line.
+/// A leading UTF-8 BOM is stripped, and CRLF line endings are normalized to LF,
+/// so each game uses LF line endings.
+///
+/// Reading errors, such as a database that isn't valid UTF-8, are yielded as an `Err`
+/// so they can be told apart from the end of the database. The iterator ends after an error.
+pub struct PgnDBIter<R: BufRead> {
+    reader: R,
+    buffer: String,
+    pending: String, // The first line of the next game, read while finding the end of the last
+    failed: bool,    // Whether a reading error has been returned
+    started: bool,   // Whether the first line of the database has been read
+    delimiter: GameDelimiter,
+}
+
+impl<R: BufRead> PgnDBIter<R> {
+    /// Creates a new PgnDBIter over the given reader.
+    pub fn new(reader: R) -> Self {
+        Self::with_delimiter(reader, GameDelimiter::default())
+    }
+
+    /// Creates a new PgnDBIter over the given reader, splitting games at the given delimiter.
+    pub fn with_delimiter(reader: R, delimiter: GameDelimiter) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            pending: String::new(),
+            failed: false,
+            started: false,
+            delimiter,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnDBIter<R> {
+    // The type of the elements being iterated over.
+    type Item = std::io::Result<String>;
+
+    /// Get the next game in the database.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        // start with the line that ended the previous game, if any
+        let mut game = std::mem::take(&mut self.pending);
+        let mut prev_blank = false;
+        let mut in_movetext = false;
+
+        // read until the next game
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => break, // EOF
+                Ok(_) => {
+                    if self.buffer.ends_with("\r\n") {
+                        self.buffer.truncate(self.buffer.len() - 2);
+                        self.buffer.push('\n');
+                    }
+                    // only the first line of the database can start with a BOM
+                    if !self.started {
+                        self.started = true;
+                        if self.buffer.starts_with('\u{feff}') {
+                            self.buffer.drain(..'\u{feff}'.len_utf8());
+                        }
+                    }
+                    let blank = self.buffer.trim().is_empty();
+                    match &self.delimiter {
+                        // separators end the game, but are skipped at the start of a game
+                        GameDelimiter::Custom(separator)
+                            if self.buffer.trim_end_matches('\n') == separator =>
+                        {
+                            if game.trim().is_empty() {
+                                game.clear();
+                                continue;
+                            }
+                            break;
+                        }
+                        // stop reading if we reach a tag after a blank line, the start of the next game
+                        GameDelimiter::NextTag if prev_blank && self.buffer.starts_with('[') => {
+                            self.pending = std::mem::take(&mut self.buffer);
+                            break;
+                        }
+                        // stop reading at a blank line after the movetext
+                        GameDelimiter::BlankLine if blank && in_movetext => break,
+                        _ => (),
+                    }
+                    // skip empty lines at the start of a game
+                    if game.is_empty() && blank {
+                        continue;
+                    }
+                    // otherwise, add the line to the game
+                    prev_blank = blank;
+                    in_movetext |= !blank && !self.buffer.starts_with('[');
+                    game.push_str(&self.buffer);
+                }
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        // return the game if it's not empty
+        if game.trim().is_empty() {
+            None
+        } else {
+            Some(Ok(game))
+        }
+    }
+}
+
+/// Opens a PGN database file and returns an iterator over the games in the database.
+pub fn pgn_db_into_iter(path: &str) -> Result<PgnDBIter<BufReader<File>>, std::io::Error> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+    Ok(PgnDBIter::new(reader))
+}
+
+/// Iterator adapters for iterators over the PGN strings of a database, such as `PgnDBIter`.
+pub trait PgnGameIterExt: Iterator<Item = std::io::Result<String>> + Sized {
+    /// Lazily parses and compresses each game with the given strategy.
+    /// Each game is yielded as a record created by `to_padded_bytes` with an alignment of 1,
+    /// so it can be read back with `from_padded_bytes` and the same strategy.
+    fn compressed(self, strategy: Strategy) -> impl Iterator<Item = anyhow::Result<Vec<u8>>> {
+        self.map(move |pgn_str| {
+            let pgn_data = PgnData::from_str(&pgn_str?)?;
+            to_padded_bytes(&strategy.compress(&pgn_data)?, 1)
+        })
+    }
+}
+
+impl<I: Iterator<Item = std::io::Result<String>>> PgnGameIterExt for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Example PGN database with records that start with the White tag.
+    const PGN_DB_WHITE_FIRST: &str = r#"[White "Magnus Carlsen"]
+[Black "Samvel Ter-Sahakyan"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 1-0
+
+[White "Hikaru Nakamura"]
+[Black "Magnus Carlsen"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+"#;
+
+    #[test]
+    /// Tests that games are split at form feeds, which are dropped from the games.
+    fn form_feed_delimiter() {
+        let db = PGN_DB_WHITE_FIRST.replace("\n\n[White \"Hikaru", "\n\x0c\n[White \"Hikaru");
+        let delimiter = GameDelimiter::Custom(String::from("\x0c"));
+        let games: Vec<String> = PgnDBIter::with_delimiter(Cursor::new(db), delimiter)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(games.len(), 2);
+        assert!(games.iter().all(|game| !game.contains('\x0c')));
+        for (game, white) in games.iter().zip(["Magnus Carlsen", "Hikaru Nakamura"]) {
+            assert_eq!(PgnData::from_str(game).unwrap().headers.white, white);
+        }
+    }
+
+    #[test]
+    /// Tests that games are split at a line of dashes, even without blank lines around it.
+    fn dashes_delimiter() {
+        let db = "[White \"A\"]\n1. e4 *\n-----\n[White \"B\"]\n1. d4 *\n-----\n";
+        let delimiter = GameDelimiter::Custom(String::from("-----"));
+        let games: Vec<String> = PgnDBIter::with_delimiter(Cursor::new(db), delimiter)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            games,
+            ["[White \"A\"]\n1. e4 *\n", "[White \"B\"]\n1. d4 *\n"]
+        );
+    }
+
+    #[test]
+    /// Tests that headerless games are split at the blank line after their movetext.
+    fn blank_line_delimiter() {
+        let db = "1. e4 e5 *\n\n1. d4 d5 *\n\n\n[White \"A\"]\n\n1. c4 *\n";
+        let games: Vec<String> =
+            PgnDBIter::with_delimiter(Cursor::new(db), GameDelimiter::BlankLine)
+                .map(Result::unwrap)
+                .collect();
+        assert_eq!(
+            games,
+            ["1. e4 e5 *\n", "1. d4 d5 *\n", "[White \"A\"]\n\n1. c4 *\n"]
+        );
+        assert_eq!(PgnDBIter::new(Cursor::new(db)).count(), 2);
+    }
+
+    #[test]
+    /// Tests that games are split when records don't start with the Event tag.
+    fn splits_games_not_starting_with_event() {
+        let games: Vec<String> = PgnDBIter::new(Cursor::new(PGN_DB_WHITE_FIRST))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+        assert!(games[0].starts_with("[White \"Magnus Carlsen\"]"));
+        assert!(games[0].contains("1. e4 e5 2. Nf3 1-0"));
+        assert!(games[1].starts_with("[White \"Hikaru Nakamura\"]"));
+        assert!(games[1].contains("1. d4 d5 0-1"));
+    }
+
+    #[test]
+    /// Tests that CRLF databases are split into the same games as LF databases.
+    fn normalizes_crlf_line_endings() {
+        let crlf = PGN_DB_WHITE_FIRST.replace('\n', "\r\n");
+        let games: Vec<String> = PgnDBIter::new(Cursor::new(crlf))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expected: Vec<String> = PgnDBIter::new(Cursor::new(PGN_DB_WHITE_FIRST))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games, expected);
+    }
+
+    #[test]
+    /// Tests that games are compressed lazily and can be read back.
+    fn compressed_games_round_trip() {
+        let records: Vec<Vec<u8>> = PgnDBIter::new(Cursor::new(PGN_DB_WHITE_FIRST))
+            .compressed(Strategy::Huffman)
+            .skip(1)
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        assert_eq!(records.len(), 1);
+
+        let bit_vec = crate::compression::utils::from_padded_bytes(&records[0]).unwrap();
+        let pgn_data = Strategy::Huffman.decompress(&bit_vec).unwrap();
+        assert_eq!(pgn_data.headers.white, "Hikaru Nakamura");
+        assert_eq!(pgn_data.moves.len(), 2);
+    }
+
+    #[test]
+    /// Tests that a BOM at the start of a database is stripped from the first game.
+    fn strips_bom() {
+        let db = format!("\u{feff}\n{}", PGN_DB_WHITE_FIRST);
+        let games: Vec<String> = PgnDBIter::new(Cursor::new(db))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+        assert!(games[0].starts_with("[White \"Magnus Carlsen\"]"));
+
+        // a BOM after the first line is kept
+        let db = PGN_DB_WHITE_FIRST.replace("1. d4", "\u{feff}1. d4");
+        let games: Vec<String> = PgnDBIter::new(Cursor::new(db))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(games[1].contains("\u{feff}1. d4"));
+    }
+
+    #[test]
+    /// Tests that an empty database yields no games.
+    fn empty_database() {
+        let mut iter = PgnDBIter::new(Cursor::new("\n\n"));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    /// Tests that a database that isn't valid UTF-8 yields an error rather than no games.
+    fn non_utf8_database() {
+        let mut iter = PgnDBIter::new(Cursor::new(b"[Event \"\xff\xfe\"]\n".to_vec()));
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(iter.next().is_none());
+    }
+}
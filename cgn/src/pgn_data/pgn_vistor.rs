@@ -1,18 +1,91 @@
 use super::san_plus_wrapper::SanPlusWrapper;
 use super::PgnData;
 
+/// The default maximum number of moves in a game, including variations.
+/// The 75 move rule ends every legal game well before this.
+pub const DEFAULT_MAX_MOVES: usize = 20_000;
+
+/// The default maximum number of bytes of tag names and values in a game.
+pub const DEFAULT_MAX_HEADER_BYTES: usize = 64 * 1024;
+
 /// A visitor that collects the data from a PGN file into a PgnData struct.
+/// If a tag appears more than once, the first value is kept and the tag is recorded
+/// as a duplicate.
 pub struct PgnVisitor {
     data: PgnData,
+    extract_variations: bool, // Whether variations are collected or skipped
+    lines: Vec<Vec<SanPlusWrapper>>, // The variations currently being read, innermost last
+    variations: Vec<PgnData>, // The completed variations, flattened into games
+    seen_tags: Vec<String>,   // The tags read so far
+    duplicate_tags: Vec<String>, // The tags read more than once, in the order their duplicates were found
+    max_moves: usize,            // The maximum number of moves, including variations
+    max_header_bytes: usize,     // The maximum number of bytes of tag names and values
+    moves: usize,                // The number of moves read so far, including variations
+    header_bytes: usize,         // The number of bytes of tag names and values read so far
+    limit_exceeded: Option<String>, // The limit that was exceeded, if any
+    nags: Vec<Vec<u8>>,          // The NAGs of each mainline move
 }
 
 impl PgnVisitor {
-    /// Creates a new PgnVisitor.
+    /// Creates a new PgnVisitor that only keeps the mainline.
     pub fn new() -> PgnVisitor {
+        PgnVisitor::new_mainline_only()
+    }
+
+    /// Creates a new PgnVisitor that only keeps the mainline.
+    /// Variations (RAVs) are skipped entirely, so their moves never reach the mainline.
+    pub fn new_mainline_only() -> PgnVisitor {
+        PgnVisitor::with_limits(DEFAULT_MAX_MOVES, DEFAULT_MAX_HEADER_BYTES)
+    }
+
+    /// Creates a new PgnVisitor that only keeps the mainline, and stops collecting the game
+    /// once it has more than `max_moves` moves or `max_header_bytes` bytes of tags,
+    /// so a crafted game can't exhaust memory.
+    pub fn with_limits(max_moves: usize, max_header_bytes: usize) -> PgnVisitor {
         PgnVisitor {
             data: PgnData::new(),
+            extract_variations: false,
+            lines: Vec::new(),
+            variations: Vec::new(),
+            seen_tags: Vec::new(),
+            duplicate_tags: Vec::new(),
+            max_moves,
+            max_header_bytes,
+            moves: 0,
+            header_bytes: 0,
+            limit_exceeded: None,
+            nags: Vec::new(),
         }
     }
+
+    /// Creates a new PgnVisitor that keeps the mainline and also collects every variation
+    /// as a separate game, made up of the moves leading to the variation followed by its moves.
+    pub fn new_with_variations() -> PgnVisitor {
+        PgnVisitor {
+            extract_variations: true,
+            ..PgnVisitor::new_mainline_only()
+        }
+    }
+
+    /// Takes the variations collected while reading the last game.
+    pub fn take_variations(&mut self) -> Vec<PgnData> {
+        std::mem::take(&mut self.variations)
+    }
+
+    /// Returns a description of the limit the last game exceeded, if any.
+    pub fn limit_exceeded(&self) -> Option<&str> {
+        self.limit_exceeded.as_deref()
+    }
+
+    /// Takes the NAGs of each mainline move of the last game.
+    pub fn take_nags(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.nags)
+    }
+
+    /// Takes the duplicate tags found while reading the last game.
+    pub fn take_duplicate_tags(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.duplicate_tags)
+    }
 }
 
 impl pgn_reader::Visitor for PgnVisitor {
@@ -21,29 +94,96 @@ impl pgn_reader::Visitor for PgnVisitor {
 
     /// Called when a header is found in the PGN file.
     fn header(&mut self, _key: &[u8], _value: pgn_reader::RawHeader<'_>) {
-        // convert the key and value to strings and add them to the headers vector
+        self.header_bytes += _key.len() + _value.as_bytes().len();
+        if self.header_bytes > self.max_header_bytes && self.limit_exceeded.is_none() {
+            self.limit_exceeded = Some(format!(
+                "Game exceeds the maximum of {} header bytes",
+                self.max_header_bytes
+            ));
+        }
+        if self.limit_exceeded.is_some() {
+            return;
+        }
+
+        // convert the key and value to strings, decode_utf8 unescapes quotes and backslashes
         if let (Ok(key), Ok(value)) = (String::from_utf8(_key.to_vec()), _value.decode_utf8()) {
-            // match the key and set the corresponding field in the PgnData struct
-            match key.as_str() {
-                "Event" => self.data.headers.event = value.to_string(),
-                "Site" => self.data.headers.site = value.to_string(),
-                "Date" => self.data.headers.date = value.to_string(),
-                "Round" => self.data.headers.round = value.to_string(),
-                "White" => self.data.headers.white = value.to_string(),
-                "Black" => self.data.headers.black = value.to_string(),
-                "Result" => self.data.headers.result = value.to_string(),
-                _ => (),
+            // the first value of a tag wins, later values are only reported
+            if self.seen_tags.contains(&key) {
+                self.duplicate_tags.push(key);
+                return;
             }
+
+            // set the corresponding field in the PgnData struct
+            self.data.headers.set_tag(&key, value.to_string());
+            self.seen_tags.push(key);
         }
     }
 
+    /// Called when the headers end. Skips the moves if a limit was already exceeded.
+    fn end_headers(&mut self) -> pgn_reader::Skip {
+        pgn_reader::Skip(self.limit_exceeded.is_some())
+    }
+
     /// Called when a move is found in the PGN file.
     fn san(&mut self, _san_plus: pgn_reader::SanPlus) {
-        self.data.moves.push(SanPlusWrapper(_san_plus));
+        self.moves += 1;
+        if self.moves > self.max_moves && self.limit_exceeded.is_none() {
+            self.limit_exceeded = Some(format!(
+                "Game exceeds the maximum of {} moves",
+                self.max_moves
+            ));
+        }
+        if self.limit_exceeded.is_some() {
+            return;
+        }
+
+        match self.lines.last_mut() {
+            Some(line) => line.push(SanPlusWrapper(_san_plus)),
+            None => {
+                self.data.moves.push(SanPlusWrapper(_san_plus));
+                self.nags.push(Vec::new());
+            }
+        }
+    }
+
+    /// Called when a NAG is found in the PGN file. Only the NAGs of mainline moves are kept.
+    fn nag(&mut self, nag: pgn_reader::Nag) {
+        if self.lines.is_empty() {
+            if let Some(nags) = self.nags.last_mut() {
+                nags.push(nag.0);
+            }
+        }
+    }
+
+    /// Called when a variation starts. Skips it unless variations are being extracted.
+    fn begin_variation(&mut self) -> pgn_reader::Skip {
+        if !self.extract_variations {
+            return pgn_reader::Skip(true);
+        }
+
+        // a variation replaces the last move of the line it branches from
+        let parent = self.lines.last().unwrap_or(&self.data.moves);
+        let mut line = parent.clone();
+        line.pop();
+        self.lines.push(line);
+        pgn_reader::Skip(false)
+    }
+
+    /// Called when a variation ends. Stores it as a game without a result.
+    fn end_variation(&mut self) {
+        if let Some(moves) = self.lines.pop() {
+            let mut headers = self.data.headers.clone();
+            headers.result = String::from("*");
+            self.variations.push(PgnData { headers, moves });
+        }
     }
 
     /// Called when the game ends.
     fn end_game(&mut self) -> Self::Result {
+        // close any variations left open by a missing ')'
+        while !self.lines.is_empty() {
+            self.end_variation();
+        }
         self.data.to_owned()
     }
 }
@@ -0,0 +1,112 @@
+use super::{PgnData, SanPlusWrapper};
+use shakmaty::{san::SanPlus, Chess};
+
+/// The game termination markers allowed as the value of the Result tag.
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// What `PgnData::sanitize_for_reduced_format` changed or removed.
+/// Comments, NAGs, variations and tags outside the seven tag roster are never kept when
+/// parsing, so they are not reported here.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReducedFormatReport {
+    /// The moves rewritten to their canonical SAN, as `(ply, original, canonical)`
+    /// with 1-based plies, e.g. `(3, "Ngf3", "Nf3")`.
+    pub rewritten_moves: Vec<(usize, String, String)>,
+    /// The SAN of the moves removed from the first illegal move onwards.
+    pub removed_moves: Vec<String>,
+    /// The Result tag value replaced by `*`, if it was not a game termination marker.
+    pub replaced_result: Option<String>,
+}
+
+impl ReducedFormatReport {
+    /// Returns true if nothing was changed.
+    pub fn is_empty(&self) -> bool {
+        self.rewritten_moves.is_empty()
+            && self.removed_moves.is_empty()
+            && self.replaced_result.is_none()
+    }
+}
+
+impl PgnData {
+    /// Prepares the game for lossless compression by every strategy, returning what was changed:
+    /// * Each move is rewritten to its canonical SAN, with minimal disambiguation and the
+    ///   correct check or checkmate suffix, as the move index strategies decode it.
+    /// * The moves from the first illegal move onwards are removed, since they can't be encoded.
+    /// * A Result tag that is not `1-0`, `0-1`, `1/2-1/2` or `*`, including an empty one,
+    ///   is replaced by `*`.
+    pub fn sanitize_for_reduced_format(&mut self) -> ReducedFormatReport {
+        let mut report = ReducedFormatReport::default();
+        let mut pos = Chess::default();
+        for (i, san_plus) in self.moves.iter_mut().enumerate() {
+            let m = match san_plus.0.san.to_move(&pos) {
+                Ok(m) => m,
+                Err(_) => {
+                    report.removed_moves = self.moves[i..]
+                        .iter()
+                        .map(|san_plus| san_plus.0.to_string())
+                        .collect();
+                    self.moves.truncate(i);
+                    break;
+                }
+            };
+            let canonical = SanPlus::from_move_and_play_unchecked(&mut pos, &m);
+            if canonical != san_plus.0 {
+                report
+                    .rewritten_moves
+                    .push((i + 1, san_plus.0.to_string(), canonical.to_string()));
+                *san_plus = SanPlusWrapper(canonical);
+            }
+        }
+
+        if !RESULTS.contains(&self.headers.result.as_str()) {
+            let result = std::mem::replace(&mut self.headers.result, String::from("*"));
+            report.replaced_result = Some(result);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    /// Tests that a game already in reduced format is left unchanged.
+    fn sanitize_reduced_game() {
+        let pgn_str = "[Result \"1-0\"]\n\n1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0";
+        let mut game = PgnData::from_str(pgn_str).unwrap();
+        let expected = game.clone();
+        assert!(game.sanitize_for_reduced_format().is_empty());
+        assert!(expected.diff(&game).is_empty());
+    }
+
+    #[test]
+    /// Tests that non-canonical SAN is rewritten and then round trips through huffman.
+    fn sanitize_rewrites_moves() {
+        let mut game =
+            PgnData::from_str("1. e4 e5 2. Ngf3 Nb8c6 3. Bb5 a6 4. Bxc6 dxc6 *").unwrap();
+        let report = game.sanitize_for_reduced_format();
+        assert_eq!(
+            report.rewritten_moves,
+            [
+                (3, String::from("Ngf3"), String::from("Nf3")),
+                (4, String::from("Nb8c6"), String::from("Nc6")),
+            ]
+        );
+        let compressed = crate::compression::huffman::compress_pgn_data(&game).unwrap();
+        let decompressed = crate::compression::huffman::decompress_pgn_data(&compressed).unwrap();
+        assert!(game.diff(&decompressed).is_empty());
+    }
+
+    #[test]
+    /// Tests that moves from the first illegal move are removed and the result is replaced.
+    fn sanitize_removes_illegal_moves_and_result() {
+        let mut game = PgnData::from_str("[Result \"White wins\"]\n\n1. e4 e4 2. Nf3 *").unwrap();
+        let report = game.sanitize_for_reduced_format();
+        assert_eq!(report.removed_moves, ["e4", "Nf3"]);
+        assert_eq!(report.replaced_result.as_deref(), Some("White wins"));
+        assert_eq!(game.moves.len(), 1);
+        assert_eq!(game.headers.result, "*");
+    }
+}
@@ -6,6 +6,14 @@ use std::str::FromStr;
 #[derive(Clone, Debug)]
 pub struct SanPlusWrapper(pub SanPlus);
 
+impl SanPlusWrapper {
+    /// Parses a move in SAN, with an optional check or checkmate suffix (e.g. `Nf3`, `Qxf7#`).
+    /// The move is not checked for legality.
+    pub fn from_san_str(san: &str) -> anyhow::Result<SanPlusWrapper> {
+        Ok(SanPlusWrapper(SanPlus::from_str(san)?))
+    }
+}
+
 impl Serialize for SanPlusWrapper {
     /// Serializes the SanPlusWrapper into a string.
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -28,3 +36,23 @@ impl<'de> Deserialize<'de> for SanPlusWrapper {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Tests if moves are parsed from SAN strings.
+    fn from_san_str() {
+        assert_eq!(
+            SanPlusWrapper::from_san_str("Qxf7#").unwrap().0.to_string(),
+            "Qxf7#"
+        );
+        assert_eq!(
+            SanPlusWrapper::from_san_str("O-O").unwrap().0.to_string(),
+            "O-O"
+        );
+        assert!(SanPlusWrapper::from_san_str("Zz9").is_err());
+        assert!(SanPlusWrapper::from_san_str("").is_err());
+    }
+}